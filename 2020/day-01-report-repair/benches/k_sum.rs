@@ -0,0 +1,108 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use day_01_report_repair::find_k_sum;
+use itertools::Itertools;
+
+const ENTRIES: usize = 1_000_000;
+// The old search walks every combination, so it only gets ledgers this small.
+const OLD_SEARCH_ENTRIES: [usize; 2] = [200, 1_000];
+// Without a match to stop at, triplets are quadratic even for `find_k_sum`: an absent target
+// over 10^6 entries is ~10^12 steps, so that case stops here. A present one still runs at 10^6.
+const ABSENT_TRIPLET_ENTRIES: usize = 20_000;
+
+// A deterministic ledger: even values from a small LCG, so every run benches the same input and
+// no combination can add up to an odd target.
+fn generate(n: usize) -> Vec<i128> {
+    let mut state: u64 = 0x2020;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) % 1_000_000) as i128 * 2
+        })
+        .collect()
+}
+
+// A target in the middle of the range of sums that nothing adds up to, so both searches have to
+// give up rather than stumble on an early match.
+fn absent(k: i128) -> i128 {
+    k * 1_000_000 + 1
+}
+
+// A target picked from entries spread through the ledger, so it exists but isn't at the front.
+fn present(entries: &[i128], k: usize) -> i128 {
+    (0..k)
+        .map(|i| entries[entries.len() * (2 * i + 1) / (2 * k)])
+        .sum()
+}
+
+// What `find_correct_triplet` did before the k-sum engine: clone the input and walk every
+// combination in order until one fits.
+#[allow(clippy::unnecessary_to_owned)]
+fn tuple_combinations_triplet(entries: &[i128], target: i128) -> Option<(i128, i128, i128)> {
+    entries
+        .to_vec()
+        .into_iter()
+        .tuple_combinations()
+        .find(|(a, b, c)| a + b + c == target)
+}
+
+// ...and the same for `find_correct_pair`.
+#[allow(clippy::unnecessary_to_owned)]
+fn tuple_combinations_pair(entries: &[i128], target: i128) -> Option<(i128, i128)> {
+    entries
+        .to_vec()
+        .into_iter()
+        .tuple_combinations()
+        .find(|(a, b)| a + b == target)
+}
+
+// `find_k_sum` runs at `largest_absent` entries for the absent target and at `ENTRIES` for the
+// present one.
+fn bench_k(c: &mut Criterion, k: usize, largest_absent: usize, old: fn(&[i128], i128) -> bool) {
+    let ledger = generate(ENTRIES);
+    for (name, is_present) in [("absent", false), ("present", true)].iter().copied() {
+        let target = |entries: &[i128]| match is_present {
+            true => present(entries, k),
+            false => absent(k as i128),
+        };
+        let mut group = c.benchmark_group(format!("k{}_{}", k, name));
+        group.sample_size(10);
+        for &n in &OLD_SEARCH_ENTRIES {
+            let entries = &ledger[..n];
+            let target = target(entries);
+            group.bench_with_input(
+                BenchmarkId::new("tuple_combinations", n),
+                entries,
+                |b, e| b.iter(|| old(black_box(e), target)),
+            );
+        }
+        let largest = match is_present {
+            true => ENTRIES,
+            false => largest_absent,
+        };
+        for n in OLD_SEARCH_ENTRIES.iter().copied().chain(Some(largest)) {
+            let entries = &ledger[..n];
+            let target = target(entries);
+            group.bench_with_input(BenchmarkId::new("find_k_sum", n), entries, |b, e| {
+                b.iter(|| find_k_sum(black_box(e), k, target))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn triplets(c: &mut Criterion) {
+    bench_k(c, 3, ABSENT_TRIPLET_ENTRIES, |entries, target| {
+        tuple_combinations_triplet(entries, target).is_some()
+    });
+}
+
+fn pairs(c: &mut Criterion) {
+    bench_k(c, 2, ENTRIES, |entries, target| {
+        tuple_combinations_pair(entries, target).is_some()
+    });
+}
+
+criterion_group!(benches, triplets, pairs);
+criterion_main!(benches);
@@ -0,0 +1,548 @@
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+pub fn parse_input(s: &str) -> Result<Vec<i128>, std::num::ParseIntError> {
+    s.split_ascii_whitespace()
+        .map(str::parse::<i128>)
+        .collect::<Result<Vec<_>, _>>() // using Result<Vec> here will return Err if ANY fail to parse, else Ok(Vec)
+}
+
+/// A single expense report entry, along with the (zero-based) line it was read from.
+///
+/// Values are kept as `i128` so ledgers with entries wider than `i64` still parse. All sums are
+/// checked; a combination whose running total would leave the `i128` range is never reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub line: usize,
+    pub value: i128,
+}
+
+fn sorted_entries(entries: &[i128]) -> Vec<Entry> {
+    let mut sorted: Vec<Entry> = entries
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(line, value)| Entry { line, value })
+        .collect();
+    sorted.par_sort_unstable_by_key(|e| (e.value, e.line));
+    sorted
+}
+
+// Compare `a + b` against `target` without overflowing.
+fn cmp_sum(a: i128, b: i128, target: i128) -> Ordering {
+    match a.checked_add(b) {
+        Some(sum) => sum.cmp(&target),
+        // only two values of the same sign can overflow, and then they're past either end
+        None if a > 0 => Ordering::Greater,
+        None => Ordering::Less,
+    }
+}
+
+/// Find `k` distinct entries whose values add up to `target`.
+///
+/// Pairs are found in a single pass with a hash map of values seen so far. For larger `k` the
+/// entries are sorted once, then the search fixes the smallest remaining entry and recurses until
+/// only two are left, which are found with a two-pointer sweep. That puts the cost at O(n) for
+/// k <= 2 and O(n^(k-1)) beyond that, with the outermost loop spread across cores.
+///
+/// The matching entries are returned in line order.
+pub fn find_k_sum(entries: &[i128], k: usize, target: i128) -> Option<Vec<Entry>> {
+    let mut found = match k {
+        0 => (target == 0).then(Vec::new)?,
+        1 => {
+            let line = entries.iter().position(|&value| value == target)?;
+            vec![Entry {
+                line,
+                value: target,
+            }]
+        }
+        2 => pair_by_hash(entries, target)?,
+        _ => {
+            let sorted = sorted_entries(entries);
+            sorted
+                .par_iter()
+                .enumerate()
+                .filter(|&(i, entry)| i == 0 || sorted[i - 1].value != entry.value)
+                .find_map_first(|(i, entry)| {
+                    let rest = target.checked_sub(entry.value)?;
+                    let mut picked = vec![*entry];
+                    k_sum_sorted(&sorted[i + 1..], k - 1, rest, &mut picked).then_some(picked)
+                })?
+        }
+    };
+    found.sort_unstable_by_key(|e| e.line);
+    Some(found)
+}
+
+fn pair_by_hash(entries: &[i128], target: i128) -> Option<Vec<Entry>> {
+    let mut seen = HashMap::with_capacity(entries.len());
+    for (line, &value) in entries.iter().enumerate() {
+        if let Some(&other) = target.checked_sub(value).and_then(|need| seen.get(&need)) {
+            return Some(vec![
+                Entry {
+                    line: other,
+                    value: target - value,
+                },
+                Entry { line, value },
+            ]);
+        }
+        seen.entry(value).or_insert(line);
+    }
+    None
+}
+
+// `sorted` must be ordered by value. On success, `picked` holds the chosen entries.
+fn k_sum_sorted(sorted: &[Entry], k: usize, target: i128, picked: &mut Vec<Entry>) -> bool {
+    match k {
+        0 => target == 0,
+        1 => match sorted.binary_search_by_key(&target, |e| e.value) {
+            Ok(i) => {
+                picked.push(sorted[i]);
+                true
+            }
+            Err(_) => false,
+        },
+        2 => {
+            if sorted.len() < 2 {
+                return false;
+            }
+            let (mut lo, mut hi) = (0, sorted.len() - 1);
+            while lo < hi {
+                match cmp_sum(sorted[lo].value, sorted[hi].value, target) {
+                    Ordering::Less => lo += 1,
+                    Ordering::Greater => hi -= 1,
+                    Ordering::Equal => {
+                        picked.push(sorted[lo]);
+                        picked.push(sorted[hi]);
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+        _ => {
+            for (i, entry) in sorted.iter().enumerate() {
+                // an equal value one slot earlier has already been tried with a superset of what's left
+                if i > 0 && sorted[i - 1].value == entry.value {
+                    continue;
+                }
+                let rest = match target.checked_sub(entry.value) {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                picked.push(*entry);
+                if k_sum_sorted(&sorted[i + 1..], k - 1, rest, picked) {
+                    return true;
+                }
+                picked.pop();
+            }
+            false
+        }
+    }
+}
+
+// Entries sharing a value. Keeping them together lets the search treat the input as a
+// multiset: each value is a single branch, and the lines behind it are only expanded at the end.
+struct ValueGroup {
+    value: i128,
+    entries: Vec<Entry>,
+}
+
+fn group_by_value(entries: &[i128]) -> Vec<ValueGroup> {
+    sorted_entries(entries)
+        .into_iter()
+        .group_by(|e| e.value)
+        .into_iter()
+        .map(|(value, group)| ValueGroup {
+            value,
+            entries: group.collect(),
+        })
+        .collect()
+}
+
+// (group index, how many entries of that group) for each value used in a solution.
+type ValueSum = Vec<(usize, usize)>;
+
+// Every way of picking `k` values from `groups` that sums to `target`. For k >= 3 the choice of
+// the first value is spread across cores; solutions still come back in the sequential order.
+fn all_value_sums(groups: &[ValueGroup], k: usize, target: i128) -> Vec<ValueSum> {
+    if k < 3 {
+        let mut out = vec![];
+        value_sums(groups, 0, k, target, &mut vec![], &mut out);
+        return out;
+    }
+
+    groups
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(i, group)| {
+            let mut out = vec![];
+            for count in 1..=group.entries.len().min(k) {
+                if let Some(rest) = (group.value)
+                    .checked_mul(count as i128)
+                    .and_then(|used| target.checked_sub(used))
+                {
+                    value_sums(
+                        groups,
+                        i + 1,
+                        k - count,
+                        rest,
+                        &mut vec![(i, count)],
+                        &mut out,
+                    );
+                }
+            }
+            out
+        })
+        .collect()
+}
+
+// Collect every way of picking `k` values from `groups[start..]` that sums to `target`.
+// A group is never asked for more entries than it has.
+fn value_sums(
+    groups: &[ValueGroup],
+    start: usize,
+    k: usize,
+    target: i128,
+    taken: &mut ValueSum,
+    out: &mut Vec<ValueSum>,
+) {
+    match k {
+        0 => {
+            if target == 0 {
+                out.push(taken.clone());
+            }
+        }
+        1 => {
+            if let Ok(i) = groups[start..].binary_search_by_key(&target, |g| g.value) {
+                taken.push((start + i, 1));
+                out.push(taken.clone());
+                taken.pop();
+            }
+        }
+        2 => {
+            if start >= groups.len() {
+                return;
+            }
+            let (mut lo, mut hi) = (start, groups.len() - 1);
+            while lo <= hi {
+                match cmp_sum(groups[lo].value, groups[hi].value, target) {
+                    Ordering::Less => lo += 1,
+                    Ordering::Greater if hi == lo => break,
+                    Ordering::Greater => hi -= 1,
+                    Ordering::Equal => {
+                        if lo == hi {
+                            if groups[lo].entries.len() >= 2 {
+                                taken.push((lo, 2));
+                                out.push(taken.clone());
+                                taken.pop();
+                            }
+                            break;
+                        }
+                        taken.push((lo, 1));
+                        taken.push((hi, 1));
+                        out.push(taken.clone());
+                        taken.truncate(taken.len() - 2);
+                        lo += 1;
+                        hi -= 1;
+                    }
+                }
+            }
+        }
+        _ => {
+            for (i, group) in groups.iter().enumerate().skip(start) {
+                for count in 1..=group.entries.len().min(k) {
+                    let rest = match (group.value)
+                        .checked_mul(count as i128)
+                        .and_then(|used| target.checked_sub(used))
+                    {
+                        Some(rest) => rest,
+                        None => continue,
+                    };
+                    taken.push((i, count));
+                    value_sums(groups, i + 1, k - count, rest, taken, out);
+                    taken.pop();
+                }
+            }
+        }
+    }
+}
+
+// Every combination of lines behind one value-level solution.
+fn expand(groups: Vec<(Vec<Entry>, usize)>) -> Vec<Vec<Entry>> {
+    groups
+        .into_iter()
+        .fold(vec![vec![]], |partials, (entries, count)| {
+            let choices: Vec<Vec<Entry>> = entries.into_iter().combinations(count).collect();
+            partials
+                .iter()
+                .flat_map(|partial| {
+                    choices
+                        .iter()
+                        .map(move |choice| partial.iter().chain(choice).copied().collect())
+                })
+                .collect()
+        })
+        .into_iter()
+        .map(|mut combination: Vec<Entry>| {
+            combination.sort_unstable_by_key(|e| e.line);
+            combination
+        })
+        .collect()
+}
+
+/// Iterate over every distinct combination of `k` entries whose values add up to `target`.
+///
+/// The input is treated as a multiset: no entry is used twice, but equal values on different
+/// lines are separate entries, so `[1010, 1010, 1010]` has three pairs summing to 2020.
+pub fn k_sum_combinations(
+    entries: &[i128],
+    k: usize,
+    target: i128,
+) -> impl Iterator<Item = Vec<Entry>> {
+    let groups = group_by_value(entries);
+    let solutions = all_value_sums(&groups, k, target);
+
+    solutions.into_iter().flat_map(move |solution| {
+        expand(
+            solution
+                .into_iter()
+                .map(|(g, count)| (groups[g].entries.clone(), count))
+                .collect(),
+        )
+    })
+}
+
+/// Count the combinations [`k_sum_combinations`] would yield, without building any of them.
+pub fn count_k_sums(entries: &[i128], k: usize, target: i128) -> usize {
+    let groups = group_by_value(entries);
+
+    all_value_sums(&groups, k, target)
+        .iter()
+        .map(|solution| {
+            solution
+                .iter()
+                .map(|&(g, count)| binomial(groups[g].entries.len(), count))
+                .product::<usize>()
+        })
+        .sum()
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+// How far a candidate sum landed from what was wanted, and the entries that got there.
+type Candidate = (u128, Vec<Entry>);
+
+/// Find the `k` entries whose sum is nearest to `target`, in line order.
+///
+/// Useful when nothing matches exactly. Ties go to whichever combination the sequential search
+/// would reach first. Returns `None` only when there are fewer than `k` entries.
+pub fn closest_k_sum(entries: &[i128], k: usize, target: i128) -> Option<Vec<Entry>> {
+    let sorted = sorted_entries(entries);
+
+    let best = if k < 3 {
+        let mut best = None;
+        closest_sorted(&sorted, k, target, &mut vec![], &mut best);
+        best
+    } else {
+        sorted
+            .par_iter()
+            .enumerate()
+            .filter(|&(i, entry)| i == 0 || sorted[i - 1].value != entry.value)
+            .filter_map(|(i, entry)| {
+                let mut best = None;
+                let rest = target.saturating_sub(entry.value);
+                closest_sorted(&sorted[i + 1..], k - 1, rest, &mut vec![*entry], &mut best);
+                best.map(|(distance, found)| (distance, i, found))
+            })
+            .min_by_key(|(distance, i, _)| (*distance, *i))
+            .map(|(distance, _, found)| (distance, found))
+    };
+
+    best.map(|(_, mut found)| {
+        found.sort_unstable_by_key(|e| e.line);
+        found
+    })
+}
+
+fn closest_sorted(
+    sorted: &[Entry],
+    k: usize,
+    rest: i128,
+    picked: &mut Vec<Entry>,
+    best: &mut Option<Candidate>,
+) {
+    if sorted.len() < k {
+        return;
+    }
+    match k {
+        0 => offer(picked, &[], rest.unsigned_abs(), best),
+        1 => {
+            // only the values either side of `rest` can be closest
+            let i = sorted.partition_point(|e| e.value < rest);
+            for e in sorted[i.saturating_sub(1)..].iter().take(2) {
+                offer(picked, &[*e], e.value.abs_diff(rest), best);
+            }
+        }
+        2 => {
+            let (mut lo, mut hi) = (0, sorted.len() - 1);
+            while lo < hi {
+                let sum = sorted[lo].value.saturating_add(sorted[hi].value);
+                offer(picked, &[sorted[lo], sorted[hi]], sum.abs_diff(rest), best);
+                match cmp_sum(sorted[lo].value, sorted[hi].value, rest) {
+                    Ordering::Less => lo += 1,
+                    Ordering::Greater => hi -= 1,
+                    Ordering::Equal => return,
+                }
+            }
+        }
+        _ => {
+            for (i, entry) in sorted.iter().enumerate() {
+                if i > 0 && sorted[i - 1].value == entry.value {
+                    continue;
+                }
+                picked.push(*entry);
+                let rest = rest.saturating_sub(entry.value);
+                closest_sorted(&sorted[i + 1..], k - 1, rest, picked, best);
+                picked.pop();
+                if matches!(best, Some((0, _))) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn offer(picked: &[Entry], extra: &[Entry], distance: u128, best: &mut Option<Candidate>) {
+    let closer = match best {
+        Some((best_distance, _)) => distance < *best_distance,
+        None => true,
+    };
+    if closer {
+        *best = Some((distance, picked.iter().chain(extra).copied().collect()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"1721
+979
+366
+299
+675
+1456"#;
+    const FULL: &str = include_str!("input.txt");
+
+    #[test]
+    fn k_sum_reports_lines() -> anyhow::Result<()> {
+        let entries = parse_input(EXAMPLE)?;
+        let found = find_k_sum(&entries, 2, 2020).unwrap();
+        let found: Vec<_> = found.iter().map(|e| (e.line, e.value)).collect();
+        assert_eq!(found, vec![(0, 1721), (3, 299)]);
+        Ok(())
+    }
+
+    #[test]
+    fn k_sum_arbitrary_target_and_size() -> anyhow::Result<()> {
+        let entries = parse_input(EXAMPLE)?;
+
+        let found = find_k_sum(&entries, 4, 979 + 366 + 299 + 675).unwrap();
+        let lines: Vec<_> = found.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4]);
+
+        let found = find_k_sum(&entries, 1, 675).unwrap();
+        assert_eq!((found[0].line, found[0].value), (4, 675));
+
+        assert_eq!(find_k_sum(&entries, 0, 0), Some(vec![]));
+        assert_eq!(find_k_sum(&entries, 2, 1), None);
+        assert_eq!(find_k_sum(&entries, 7, 5496), None);
+        Ok(())
+    }
+
+    #[test]
+    fn k_sum_does_not_reuse_an_entry() {
+        assert_eq!(find_k_sum(&[1010, 5], 2, 2020), None);
+        let found = find_k_sum(&[1010, 5, 1010], 2, 2020).unwrap();
+        assert_eq!(found.iter().map(|e| e.line).collect::<Vec<_>>(), vec![0, 2]);
+    }
+
+    #[test]
+    fn k_sum_handles_entries_wider_than_i64() -> anyhow::Result<()> {
+        let wide = i64::MAX as i128 * 4;
+        let entries = parse_input(&format!("{}\n7\n{}\n-3", wide, -wide + 10))?;
+        let found = find_k_sum(&entries, 2, 10).unwrap();
+        assert_eq!(found.iter().map(|e| e.line).collect::<Vec<_>>(), vec![0, 2]);
+        let found = find_k_sum(&entries, 3, 7).unwrap();
+        assert_eq!(
+            found.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![0, 2, 3]
+        );
+
+        // sums past i128::MAX are never mistaken for a match
+        let entries = [i128::MAX, i128::MAX, 1, -1];
+        assert_eq!(find_k_sum(&entries, 2, -2), None);
+        assert_eq!(find_k_sum(&entries, 3, -1), None);
+        assert_eq!(count_k_sums(&entries, 2, i128::MAX - 1), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn enumerates_repeated_values_as_a_multiset() {
+        let entries = [1010, 5, 1010, 1010, 2015];
+        let pairs: Vec<Vec<usize>> = k_sum_combinations(&entries, 2, 2020)
+            .map(|found| found.iter().map(|e| e.line).collect())
+            .sorted()
+            .collect();
+        assert_eq!(pairs, vec![vec![0, 2], vec![0, 3], vec![1, 4], vec![2, 3]]);
+        assert_eq!(count_k_sums(&entries, 2, 2020), 4);
+
+        // 1010 + 1010 + 0 picks two of three 1010s, 5 + 1010 + 1005 picks one of each
+        let entries = [1010, 1010, 1010, 5, 1005, 1005, 0];
+        assert_eq!(count_k_sums(&entries, 3, 3030), 1);
+        assert_eq!(count_k_sums(&entries, 3, 2020), 3 + 3 * 2);
+        assert_eq!(k_sum_combinations(&entries, 3, 2020).count(), 9);
+    }
+
+    #[test]
+    fn counts_match_brute_force() -> anyhow::Result<()> {
+        let entries = parse_input(FULL)?;
+        for k in 1..=3 {
+            for target in [2020, 1500, 3000] {
+                let brute = entries
+                    .iter()
+                    .combinations(k)
+                    .filter(|c| c.iter().copied().sum::<i128>() == target)
+                    .count();
+                assert_eq!(
+                    count_k_sums(&entries, k, target),
+                    brute,
+                    "k={} target={}",
+                    k,
+                    target
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn finds_closest_when_nothing_matches() -> anyhow::Result<()> {
+        let entries = parse_input(EXAMPLE)?;
+        assert_eq!(count_k_sums(&entries, 2, 2021), 0);
+        let found = closest_k_sum(&entries, 2, 2021).unwrap();
+        assert_eq!(found.iter().map(|e| e.value).sum::<i128>(), 2020);
+
+        let found = closest_k_sum(&entries, 3, 0).unwrap();
+        let values: Vec<_> = found.iter().map(|e| e.value).collect();
+        assert_eq!(values, vec![366, 299, 675]);
+
+        assert_eq!(closest_k_sum(&entries, 7, 0), None);
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(dead_code, unused_imports)]
+use day_01_report_repair::{find_k_sum, parse_input};
 use itertools::Itertools;
 
 fn main() -> anyhow::Result<()> {
@@ -13,27 +14,14 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn parse_input(s: &str) -> Result<Vec<i64>, std::num::ParseIntError> {
-    s.split_ascii_whitespace()
-        .map(str::parse::<i64>)
-        .collect::<Result<Vec<_>, _>>() // using Result<Vec> here will return Err if ANY fail to parse, else Ok(Vec)
+fn find_correct_pair(s: &str) -> anyhow::Result<(i128, i128)> {
+    let found = find_k_sum(&parse_input(s)?, 2, 2020).expect("no pair had a sum of 2020");
+    Ok((found[0].value, found[1].value))
 }
 
-fn find_correct_pair(s: &str) -> anyhow::Result<(i64, i64)> {
-    Ok(parse_input(s)?
-            .into_iter()
-            .tuple_combinations()
-            .find(|(a, b)| a + b == 2020)
-            .expect("no pair had a sum of 2020"))
-}
-
-fn find_correct_triplet(s: &str) -> anyhow::Result<(i64, i64, i64)> {
-    Ok(parse_input(s)?
-    .into_iter()
-    .tuple_combinations()
-    .find(|(a, b, c)| a + b + c == 2020)
-    .expect("no triplet had a sum of 2020")
-)
+fn find_correct_triplet(s: &str) -> anyhow::Result<(i128, i128, i128)> {
+    let found = find_k_sum(&parse_input(s)?, 3, 2020).expect("no triplet had a sum of 2020");
+    Ok((found[0].value, found[1].value, found[2].value))
 }
 
 #[cfg(test)]
@@ -46,7 +34,7 @@ mod tests {
 299
 675
 1456"#;
-const FULL: &str = include_str!("input.txt");
+    const FULL: &str = include_str!("input.txt");
 
     #[test]
     fn does_pt1_example() -> anyhow::Result<()> {
@@ -117,4 +105,4 @@ fn find_pair_whose_sum_is_2020(s: Vec<i64>) -> Option<(i64, i64)> {
     all_pairs(&s[..]).into_iter().find(|(a, b)| a + b == 2020)
 }
 
-*/
\ No newline at end of file
+*/
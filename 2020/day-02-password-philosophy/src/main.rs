@@ -2,46 +2,522 @@
 #![allow(unused_variables)]
 #![allow(clippy::bool_assert_comparison)]
 
-mod part_one {
-    use std::ops::RangeInclusive;
+pub mod policy {
+    use rand::seq::SliceRandom;
+    use rand::{Rng, RngCore};
+    use std::collections::HashSet;
 
-    #[derive(PartialEq, Debug)]
-    pub struct PasswordPolicy {
-        byte: u8,
-        range: RangeInclusive<usize>,
+    /// How positions and distinct characters are counted in a password.
+    #[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+    pub enum Indexing {
+        /// Unicode scalar values, i.e. `char`s. The default.
+        #[default]
+        Chars,
+        /// Raw UTF-8 bytes. Matches the original puzzle code, and differs from `Chars` as soon
+        /// as a password has anything outside ASCII in it.
+        Bytes,
     }
 
-    impl PasswordPolicy {
-        fn is_valid(&self, password: &str) -> bool {
-            self.range.contains(
-                &password
-                    .as_bytes()
-                    .iter()
-                    .copied()
-                    .filter(|&b| b == self.byte)
-                    .count(),
-            )
+    /// The part of a line before the colon: two numbers and a letter, as in `1-3 a`.
+    ///
+    /// What the numbers mean is up to the [`Policy`] reading them.
+    #[derive(PartialEq, Debug, Clone, Copy)]
+    pub struct Rule {
+        pub first: usize,
+        pub second: usize,
+        pub letter: char,
+        pub indexing: Indexing,
+    }
+
+    impl Rule {
+        // The numbers read as 1-indexed positions, or `None` for position 0.
+        fn positions(&self) -> [Option<usize>; 2] {
+            [self.first.checked_sub(1), self.second.checked_sub(1)]
+        }
+
+        fn letter_at(&self, password: &str, position: Option<usize>) -> bool {
+            let i = match position {
+                Some(i) => i,
+                None => return false,
+            };
+            match self.indexing {
+                Indexing::Chars => password.chars().nth(i) == Some(self.letter),
+                Indexing::Bytes => password.as_bytes().get(i..).is_some_and(|rest| {
+                    rest.starts_with(self.letter.encode_utf8(&mut [0; 4]).as_bytes())
+                }),
+            }
+        }
+
+        fn occurrences(&self, password: &str) -> usize {
+            password.matches(self.letter).count()
+        }
+
+        fn len(&self, password: &str) -> usize {
+            match self.indexing {
+                Indexing::Chars => password.chars().count(),
+                Indexing::Bytes => password.len(),
+            }
+        }
+
+        fn distinct(&self, password: &str) -> usize {
+            match self.indexing {
+                Indexing::Chars => password.chars().collect::<HashSet<_>>().len(),
+                Indexing::Bytes => password.bytes().collect::<HashSet<_>>().len(),
+            }
+        }
+
+        fn unit(&self) -> &'static str {
+            match self.indexing {
+                Indexing::Chars => "character",
+                Indexing::Bytes => "byte",
+            }
+        }
+
+        // Lints shared by the policies that read the numbers as positions. `needed` is whether
+        // the letter has to be at a position for the policy to be satisfiable at all.
+        fn lint_positions(&self, password: &str, needed: bool) -> Vec<(Severity, String)> {
+            let severity = match needed {
+                true => Severity::Unsatisfiable,
+                false => Severity::Suspicious,
+            };
+            let mut lints = vec![];
+            let len = self.len(password);
+            let mut positions = vec![self.first, self.second];
+            positions.dedup();
+            for position in positions {
+                if position == 0 {
+                    lints.push((
+                        severity,
+                        "position 0 doesn't exist, positions start at 1".into(),
+                    ));
+                } else if position > len {
+                    lints.push((
+                        severity,
+                        format!(
+                            "position {} is past the end of a {}-{} password",
+                            position,
+                            len,
+                            self.unit()
+                        ),
+                    ));
+                }
+            }
+            if self.indexing == Indexing::Bytes && !password.is_ascii() {
+                lints.push((
+                    Severity::Suspicious,
+                    "password isn't ASCII, so byte positions don't line up with characters".into(),
+                ));
+            }
+            lints
+        }
+    }
+
+    impl std::fmt::Display for Rule {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}-{} {}", self.first, self.second, self.letter)
+        }
+    }
+
+    /// How bad a [`Lint`] is.
+    #[derive(serde::Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Severity {
+        /// Probably a mistake, but some passwords can still pass.
+        Suspicious,
+        /// No password on this line can pass, whatever it is.
+        Unsatisfiable,
+    }
+
+    impl std::fmt::Display for Severity {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(match self {
+                Severity::Suspicious => "suspicious",
+                Severity::Unsatisfiable => "unsatisfiable",
+            })
+        }
+    }
+
+    /// One interpretation of a [`Rule`].
+    pub trait Policy {
+        /// The name used to pick this policy at runtime, see [`by_name`].
+        fn name(&self) -> &'static str;
+
+        /// `Err` carries a human-readable reason the password fails this policy.
+        fn check(&self, rule: &Rule, password: &str) -> Result<(), String>;
+
+        fn is_valid(&self, rule: &Rule, password: &str) -> bool {
+            self.check(rule, password).is_ok()
+        }
+
+        /// Problems with `rule` itself under this policy, such as a range that can never be met.
+        /// The password is only used for its length and encoding, never checked.
+        fn lint(&self, rule: &Rule, password: &str) -> Vec<(Severity, String)> {
+            vec![]
+        }
+
+        /// A password that passes this policy under `rule` (or fails it, when `valid` is false),
+        /// or `None` if there isn't one.
+        ///
+        /// The builtin policies construct one directly. Anything else gets a blind search over
+        /// random passwords, which is fine for loose policies but may give up on strict ones.
+        fn example(&self, rule: &Rule, valid: bool, rng: &mut dyn RngCore) -> Option<String> {
+            (0..1000)
+                .map(|_| {
+                    let len = rng.gen_range(0..=12);
+                    (0..len)
+                        .map(|_| match rng.gen_bool(0.25) {
+                            true => rule.letter,
+                            false => other_letter(rule, rng),
+                        })
+                        .collect::<String>()
+                })
+                .find(|password| self.is_valid(rule, password) == valid)
+        }
+    }
+
+    /// What generated passwords are made of. A few non-ASCII letters are mixed in so that
+    /// anything built from these exercises [`Indexing::Chars`] properly.
+    pub(crate) const LETTERS: &[char] = &[
+        'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r',
+        's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'é', 'ø', 'ß', 'ж', 'λ',
+    ];
+
+    // Under byte indexing a generated password has to stay ASCII to put letters where intended.
+    fn alphabet(rule: &Rule) -> &'static [char] {
+        match rule.indexing {
+            Indexing::Chars => LETTERS,
+            Indexing::Bytes => &LETTERS[..26],
+        }
+    }
+
+    fn other_letter(rule: &Rule, rng: &mut dyn RngCore) -> char {
+        let alphabet = alphabet(rule);
+        loop {
+            let c = alphabet[rng.gen_range(0..alphabet.len())];
+            if c != rule.letter {
+                return c;
+            }
+        }
+    }
+
+    // `len` random letters, none of them the rule's.
+    fn filler(rule: &Rule, len: usize, rng: &mut dyn RngCore) -> Vec<char> {
+        (0..len).map(|_| other_letter(rule, rng)).collect()
+    }
+
+    // Random filler with the rule's letter placed (or kept away from) each position.
+    fn with_positions(rule: &Rule, hits: [bool; 2], rng: &mut dyn RngCore) -> Option<String> {
+        let positions = rule.positions();
+        if positions[0] == positions[1] && hits[0] != hits[1] {
+            return None;
+        }
+        if rule.indexing == Indexing::Bytes && !rule.letter.is_ascii() && hits.contains(&true) {
+            return None;
+        }
+
+        let mut len = rng.gen_range(0..=4);
+        for (position, hit) in positions.iter().zip(hits) {
+            match position {
+                Some(i) => len = len.max(i + 1 + rng.gen_range(0..=2)),
+                None if hit => return None,
+                None => {}
+            }
+        }
+
+        let mut password = filler(rule, len, rng);
+        for (position, hit) in positions.iter().zip(hits) {
+            if let (Some(i), true) = (position, hit) {
+                password[*i] = rule.letter;
+            }
+        }
+        Some(password.into_iter().collect())
+    }
+
+    /// Part one: the letter appears between `first` and `second` times, inclusive.
+    pub struct CountInRange;
+
+    impl Policy for CountInRange {
+        fn name(&self) -> &'static str {
+            "count-in-range"
+        }
+
+        fn check(&self, rule: &Rule, password: &str) -> Result<(), String> {
+            let count = rule.occurrences(password);
+            if (rule.first..=rule.second).contains(&count) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "'{}' appears {} times, allowed {}-{}",
+                    rule.letter, count, rule.first, rule.second
+                ))
+            }
+        }
+
+        fn lint(&self, rule: &Rule, password: &str) -> Vec<(Severity, String)> {
+            if rule.first > rule.second {
+                vec![(
+                    Severity::Unsatisfiable,
+                    format!("range {}-{} is reversed", rule.first, rule.second),
+                )]
+            } else {
+                vec![]
+            }
+        }
+
+        fn example(&self, rule: &Rule, valid: bool, rng: &mut dyn RngCore) -> Option<String> {
+            let count = if valid {
+                if rule.first > rule.second {
+                    return None;
+                }
+                rng.gen_range(rule.first..=rule.second)
+            } else if rule.first > 0 && rng.gen_bool(0.5) {
+                rng.gen_range(0..rule.first)
+            } else {
+                rng.gen_range(rule.second + 1..=rule.second + 3)
+            };
+
+            let len = rng.gen_range(0..=8);
+            let mut password = filler(rule, len, rng);
+            for _ in 0..count {
+                let at = rng.gen_range(0..=password.len());
+                password.insert(at, rule.letter);
+            }
+            Some(password.into_iter().collect())
         }
     }
 
-    fn parse_line(s: &str) -> anyhow::Result<(PasswordPolicy, &str)> {
+    /// Part two: the letter is at exactly one of the 1-indexed positions `first` and `second`.
+    pub struct PositionalXor;
+
+    impl Policy for PositionalXor {
+        fn name(&self) -> &'static str {
+            "positional-xor"
+        }
+
+        fn check(&self, rule: &Rule, password: &str) -> Result<(), String> {
+            let [first, second] = rule.positions();
+            match (
+                rule.letter_at(password, first),
+                rule.letter_at(password, second),
+            ) {
+                (true, false) | (false, true) => Ok(()),
+                (true, true) => Err(format!(
+                    "'{}' is at both positions {} and {}",
+                    rule.letter, rule.first, rule.second
+                )),
+                (false, false) => Err(format!(
+                    "'{}' is at neither position {} nor {}",
+                    rule.letter, rule.first, rule.second
+                )),
+            }
+        }
+
+        fn lint(&self, rule: &Rule, password: &str) -> Vec<(Severity, String)> {
+            if rule.first == rule.second {
+                return vec![(
+                    Severity::Unsatisfiable,
+                    format!(
+                        "positions {} and {} are the same, so '{}' can't be at exactly one",
+                        rule.first, rule.second, rule.letter
+                    ),
+                )];
+            }
+            // one position missing just means the other has to match
+            let missing = |position| position == 0 || position > rule.len(password);
+            rule.lint_positions(password, missing(rule.first) && missing(rule.second))
+        }
+
+        fn example(&self, rule: &Rule, valid: bool, rng: &mut dyn RngCore) -> Option<String> {
+            let one = rng.gen_bool(0.5);
+            let hits = if valid { [one, !one] } else { [one, one] };
+            with_positions(rule, hits, rng)
+        }
+    }
+
+    /// The letter is at both of the 1-indexed positions `first` and `second`.
+    pub struct BothPositions;
+
+    impl Policy for BothPositions {
+        fn name(&self) -> &'static str {
+            "both-positions"
+        }
+
+        fn check(&self, rule: &Rule, password: &str) -> Result<(), String> {
+            let [first, second] = rule.positions();
+            match (
+                rule.letter_at(password, first),
+                rule.letter_at(password, second),
+            ) {
+                (true, true) => Ok(()),
+                (false, true) => Err(format!(
+                    "'{}' is not at position {}",
+                    rule.letter, rule.first
+                )),
+                (true, false) => Err(format!(
+                    "'{}' is not at position {}",
+                    rule.letter, rule.second
+                )),
+                (false, false) => Err(format!(
+                    "'{}' is at neither position {} nor {}",
+                    rule.letter, rule.first, rule.second
+                )),
+            }
+        }
+
+        fn lint(&self, rule: &Rule, password: &str) -> Vec<(Severity, String)> {
+            let mut lints = rule.lint_positions(password, true);
+            if rule.first == rule.second {
+                lints.push((
+                    Severity::Suspicious,
+                    format!(
+                        "positions {} and {} are the same, so only one is checked",
+                        rule.first, rule.second
+                    ),
+                ));
+            }
+            lints
+        }
+
+        fn example(&self, rule: &Rule, valid: bool, rng: &mut dyn RngCore) -> Option<String> {
+            let hits = if valid {
+                [true, true]
+            } else {
+                *[[true, false], [false, true], [false, false]].choose(rng)?
+            };
+            with_positions(rule, hits, rng)
+        }
+    }
+
+    /// The password uses at least `first` distinct characters. `second` and the letter are ignored.
+    pub struct MinDistinct;
+
+    impl Policy for MinDistinct {
+        fn name(&self) -> &'static str {
+            "min-distinct"
+        }
+
+        fn check(&self, rule: &Rule, password: &str) -> Result<(), String> {
+            let distinct = rule.distinct(password);
+            if distinct >= rule.first {
+                Ok(())
+            } else {
+                Err(format!(
+                    "uses {} distinct {}s, needs at least {}",
+                    distinct,
+                    rule.unit(),
+                    rule.first
+                ))
+            }
+        }
+
+        fn lint(&self, rule: &Rule, password: &str) -> Vec<(Severity, String)> {
+            if rule.first == 0 {
+                vec![(
+                    Severity::Suspicious,
+                    "at least 0 distinct characters lets every password through".into(),
+                )]
+            } else {
+                vec![]
+            }
+        }
+
+        fn example(&self, rule: &Rule, valid: bool, rng: &mut dyn RngCore) -> Option<String> {
+            let alphabet = alphabet(rule);
+            let distinct = if valid {
+                if rule.first > alphabet.len() {
+                    return None;
+                }
+                rng.gen_range(rule.first..=alphabet.len().min(rule.first + 3))
+            } else {
+                if rule.first == 0 {
+                    return None;
+                }
+                rng.gen_range(0..rule.first).min(alphabet.len())
+            };
+
+            let mut letters = alphabet.to_vec();
+            letters.shuffle(rng);
+            letters.truncate(distinct);
+            let mut password = letters.clone();
+            if !letters.is_empty() {
+                for _ in 0..rng.gen_range(0..=4) {
+                    password.push(*letters.choose(rng)?);
+                }
+            }
+            password.shuffle(rng);
+            Some(password.into_iter().collect())
+        }
+    }
+
+    /// The letter doesn't appear in the password at all. The numbers are ignored.
+    pub struct Forbidden;
+
+    impl Policy for Forbidden {
+        fn name(&self) -> &'static str {
+            "forbidden"
+        }
+
+        fn check(&self, rule: &Rule, password: &str) -> Result<(), String> {
+            match rule.occurrences(password) {
+                0 => Ok(()),
+                count => Err(format!(
+                    "'{}' is forbidden but appears {} times",
+                    rule.letter, count
+                )),
+            }
+        }
+
+        fn example(&self, rule: &Rule, valid: bool, rng: &mut dyn RngCore) -> Option<String> {
+            let len = rng.gen_range(0..=8);
+            let mut password = filler(rule, len, rng);
+            if !valid {
+                for _ in 0..rng.gen_range(1..=3) {
+                    let at = rng.gen_range(0..=password.len());
+                    password.insert(at, rule.letter);
+                }
+            }
+            Some(password.into_iter().collect())
+        }
+    }
+
+    /// Every policy that can be picked by name.
+    pub const POLICIES: &[&dyn Policy] = &[
+        &CountInRange,
+        &PositionalXor,
+        &BothPositions,
+        &MinDistinct,
+        &Forbidden,
+    ];
+
+    pub fn by_name(name: &str) -> Option<&'static dyn Policy> {
+        POLICIES
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == name)
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    pub enum ParseError {
+        #[error("{0}")]
+        Grammar(#[from] peg::error::ParseError<peg::str::LineCol>),
+    }
+
+    /// Parse one dump line. The rule comes back with the default [`Indexing::Chars`].
+    pub fn parse_line(s: &str) -> Result<(Rule, &str), ParseError> {
         peg::parser! {
             grammar parser() for str {
                 rule number() -> usize
-                 = n:$(['0'..='9']+) { n.parse().unwrap() }
-
-                rule range() -> RangeInclusive<usize>
-                 = min:number() "-" max:number() { min..=max }
+                 = n:$(['0'..='9']+) {? n.parse().or(Err("number")) }
 
-                rule byte() -> u8
-                 = letter:$(['a'..='z']) { letter.as_bytes()[0] }
+                rule letter() -> char
+                 = [c if !c.is_whitespace()]
 
                 rule password() -> &'input str
                  = letters:$([_]*) { letters }
 
-                pub(crate) rule line() -> (PasswordPolicy, &'input str)
-                 = range:range() " " byte:byte() ": " password:password() {
-                    (PasswordPolicy { range, byte }, password)
+                pub(crate) rule line() -> (Rule, &'input str)
+                 = first:number() "-" second:number() " " letter:letter() ": " password:password() {
+                    (Rule { first, second, letter, indexing: Indexing::Chars }, password)
                  }
             }
         }
@@ -49,132 +525,537 @@ mod part_one {
         Ok(parser::line(s)?)
     }
 
-    pub fn run(s: &str) -> usize {
+    fn parse_line_with(s: &str, indexing: Indexing) -> Result<(Rule, &str), ParseError> {
+        let (rule, password) = parse_line(s)?;
+        Ok((Rule { indexing, ..rule }, password))
+    }
+
+    /// Count the lines of `s` whose password satisfies `policy`.
+    pub fn run(s: &str, policy: &dyn Policy, indexing: Indexing) -> usize {
         s.lines()
-            .map(parse_line)
+            .map(|line| parse_line_with(line, indexing))
             .map(Result::unwrap)
-            .filter(|(policy, password)| policy.is_valid(password))
+            .filter(|(rule, password)| policy.is_valid(rule, password))
             .count()
     }
 
+    /// Why one line of a password dump didn't pass.
+    #[derive(serde::Serialize, PartialEq, Debug)]
+    pub struct Finding<'a> {
+        /// 1-indexed, to match what an editor shows.
+        pub line: usize,
+        pub policy: &'static str,
+        /// `None` when the line couldn't be parsed far enough to find one.
+        pub password: Option<&'a str>,
+        pub reason: String,
+    }
+
+    impl std::fmt::Display for Finding<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self.password {
+                Some(password) => write!(
+                    f,
+                    "line {} [{}] {:?}: {}",
+                    self.line, self.policy, password, self.reason
+                ),
+                None => write!(
+                    f,
+                    "line {} [{}] unparseable: {}",
+                    self.line, self.policy, self.reason
+                ),
+            }
+        }
+    }
+
+    /// Every line of a dump that failed `policy` or couldn't be parsed, in input order.
+    #[derive(PartialEq, Debug)]
+    pub struct Report<'a> {
+        pub policy: &'static str,
+        pub checked: usize,
+        pub valid: usize,
+        pub findings: Vec<Finding<'a>>,
+    }
+
+    impl Report<'_> {
+        /// One JSON object per finding, newline-separated.
+        pub fn to_json_lines(&self) -> String {
+            self.findings
+                .iter()
+                .map(|finding| serde_json::to_string(finding).expect("findings always serialize"))
+                .map(|line| line + "\n")
+                .collect()
+        }
+    }
+
+    impl std::fmt::Display for Report<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for finding in &self.findings {
+                writeln!(f, "{}", finding)?;
+            }
+            writeln!(
+                f,
+                "{}: {} of {} passwords are valid",
+                self.policy, self.valid, self.checked
+            )
+        }
+    }
+
+    /// Like [`run`], but keeps the reason for every line that didn't count.
+    pub fn report<'a>(s: &'a str, policy: &dyn Policy, indexing: Indexing) -> Report<'a> {
+        let mut report = Report {
+            policy: policy.name(),
+            checked: 0,
+            valid: 0,
+            findings: vec![],
+        };
+
+        for (index, line) in s.lines().enumerate() {
+            report.checked += 1;
+            let (password, reason) = match parse_line_with(line, indexing) {
+                Ok((rule, password)) => match policy.check(&rule, password) {
+                    Ok(()) => {
+                        report.valid += 1;
+                        continue;
+                    }
+                    Err(reason) => (Some(password), reason),
+                },
+                Err(e) => (None, e.to_string()),
+            };
+            report.findings.push(Finding {
+                line: index + 1,
+                policy: policy.name(),
+                password,
+                reason,
+            });
+        }
+
+        report
+    }
+
+    /// A line whose rule looks wrong under some policy, found before any password is checked.
+    #[derive(serde::Serialize, PartialEq, Debug)]
+    pub struct Lint<'a> {
+        /// 1-indexed, to match what an editor shows.
+        pub line: usize,
+        pub policy: &'static str,
+        pub password: &'a str,
+        pub severity: Severity,
+        pub message: String,
+    }
+
+    impl std::fmt::Display for Lint<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "line {} [{}] {}: {}",
+                self.line, self.policy, self.severity, self.message
+            )
+        }
+    }
+
+    /// Every rule in `s` that `policy` considers unsatisfiable or suspicious, in input order.
+    ///
+    /// Lines that don't parse are skipped; [`report`] covers those.
+    pub fn lint<'a>(s: &'a str, policy: &dyn Policy, indexing: Indexing) -> Vec<Lint<'a>> {
+        s.lines()
+            .enumerate()
+            .filter_map(|(index, line)| Some((index, parse_line_with(line, indexing).ok()?)))
+            .flat_map(|(index, (rule, password))| {
+                policy
+                    .lint(&rule, password)
+                    .into_iter()
+                    .map(move |(severity, message)| Lint {
+                        line: index + 1,
+                        policy: policy.name(),
+                        password,
+                        severity,
+                        message,
+                    })
+            })
+            .collect()
+    }
+
     #[cfg(test)]
-    mod part_one_tests {
+    mod policy_tests {
         use super::*;
 
+        const RULE: Rule = Rule {
+            first: 1,
+            second: 3,
+            letter: 'a',
+            indexing: Indexing::Chars,
+        };
+
         #[test]
-        fn test_is_valid() {
-            let pp = PasswordPolicy {
-                range: 1..=3,
-                byte: b'a',
-            };
-            assert_eq!(pp.is_valid("zeus"), false, "no 'a's");
-            assert_eq!(pp.is_valid("hades"), true, "single 'a'");
-            assert_eq!(pp.is_valid("banana"), true, "three 'a's");
-            assert_eq!(pp.is_valid("aaaah"), false, "too many 'a's");
+        fn test_parse() {
+            assert_eq!(parse_line("1-3 a: banana").unwrap(), (RULE, "banana"));
         }
 
         #[test]
-        fn test_parse() {
+        fn test_both_positions() {
+            assert_eq!(BothPositions.is_valid(&RULE, "abacus"), true);
+            assert_eq!(BothPositions.is_valid(&RULE, "abcde"), false);
+            assert_eq!(BothPositions.is_valid(&RULE, "a"), false, "too short");
+        }
+
+        #[test]
+        fn test_min_distinct() {
+            let rule = Rule { first: 3, ..RULE };
+            assert_eq!(MinDistinct.is_valid(&rule, "banana"), true);
+            assert_eq!(MinDistinct.is_valid(&rule, "aabbaa"), false);
+        }
+
+        #[test]
+        fn test_forbidden() {
+            assert_eq!(Forbidden.is_valid(&RULE, "zeus"), true);
+            assert_eq!(Forbidden.is_valid(&RULE, "hades"), false);
+        }
+
+        #[test]
+        fn test_report() {
+            let report = report(
+                "1-3 a: abcde\n1-3 a: aaaaa\n1-3 b cdefg",
+                &CountInRange,
+                Indexing::Chars,
+            );
+            assert_eq!(report.checked, 3);
+            assert_eq!(report.valid, 1);
+            assert_eq!(report.findings.len(), 2);
+            assert_eq!(
+                report.findings[0],
+                Finding {
+                    line: 2,
+                    policy: "count-in-range",
+                    password: Some("aaaaa"),
+                    reason: "'a' appears 5 times, allowed 1-3".to_string(),
+                }
+            );
+            assert_eq!(report.findings[1].line, 3);
+            assert_eq!(report.findings[1].password, None);
+            assert_eq!(report.findings[1].reason, "error at 1:6: expected \": \"");
+
+            // a number too big for a position is unparseable too, not a panic
+            let oversized = super::report(
+                "99999999999999999999-3 a: abc",
+                &CountInRange,
+                Indexing::Chars,
+            );
+            assert_eq!(oversized.findings.len(), 1);
+            assert_eq!(oversized.findings[0].password, None);
+            assert_eq!(
+                oversized.findings[0].reason,
+                "error at 1:21: expected one of ['0'..='9'], number"
+            );
+
+            assert_eq!(
+                report.to_json_lines().lines().next().unwrap(),
+                r#"{"line":2,"policy":"count-in-range","password":"aaaaa","reason":"'a' appears 5 times, allowed 1-3"}"#
+            );
+            assert_eq!(
+                report.to_string().lines().next().unwrap(),
+                r#"line 2 [count-in-range] "aaaaa": 'a' appears 5 times, allowed 1-3"#
+            );
+        }
+
+        #[test]
+        fn test_reasons() {
+            assert_eq!(
+                PositionalXor.check(&RULE, "abacus"),
+                Err("'a' is at both positions 1 and 3".to_string())
+            );
+            assert_eq!(
+                BothPositions.check(&RULE, "abcde"),
+                Err("'a' is not at position 3".to_string())
+            );
+            let rule = Rule { first: 3, ..RULE };
             assert_eq!(
-                parse_line("1-3 a: banana").unwrap(),
-                (
-                    PasswordPolicy {
-                        range: 1..=3,
-                        byte: b'a'
+                MinDistinct.check(&rule, "aabb"),
+                Err("uses 2 distinct characters, needs at least 3".to_string())
+            );
+            assert_eq!(
+                Forbidden.check(&RULE, "banana"),
+                Err("'a' is forbidden but appears 3 times".to_string())
+            );
+        }
+
+        #[test]
+        fn test_unicode() {
+            let (rule, password) = parse_line("2-3 é: aéé").unwrap();
+            assert_eq!(rule.letter, 'é');
+            assert_eq!(BothPositions.is_valid(&rule, password), true);
+            assert_eq!(CountInRange.is_valid(&rule, password), true);
+
+            let bytes = Rule {
+                indexing: Indexing::Bytes,
+                ..rule
+            };
+            assert_eq!(
+                BothPositions.check(&bytes, password),
+                Err("'é' is not at position 3".to_string())
+            );
+            assert_eq!(CountInRange.is_valid(&bytes, password), true);
+
+            let rule = Rule { first: 2, ..RULE };
+            assert_eq!(MinDistinct.is_valid(&rule, "éé"), false);
+            assert_eq!(
+                MinDistinct.is_valid(
+                    &Rule {
+                        indexing: Indexing::Bytes,
+                        ..rule
                     },
-                    "banana"
-                )
+                    "éé"
+                ),
+                true
             );
         }
+
+        #[test]
+        fn test_lint() {
+            let dump = "5-3 a: aaaa\n0-2 a: ba\n2-2 a: ba\n1-9 a: abc\n1-2 a: ab";
+            let found = |policy: &dyn Policy| -> Vec<(usize, Severity)> {
+                lint(dump, policy, Indexing::Chars)
+                    .iter()
+                    .map(|lint| (lint.line, lint.severity))
+                    .collect()
+            };
+            assert_eq!(found(&CountInRange), vec![(1, Severity::Unsatisfiable)]);
+            assert_eq!(
+                found(&PositionalXor),
+                vec![
+                    (1, Severity::Suspicious),
+                    (2, Severity::Suspicious),
+                    (3, Severity::Unsatisfiable),
+                    (4, Severity::Suspicious),
+                ]
+            );
+            assert_eq!(
+                found(&BothPositions),
+                vec![
+                    (1, Severity::Unsatisfiable),
+                    (2, Severity::Unsatisfiable),
+                    (3, Severity::Suspicious),
+                    (4, Severity::Unsatisfiable),
+                ]
+            );
+            assert_eq!(found(&Forbidden), vec![]);
+
+            let lints = lint("1-9 a: abc", &PositionalXor, Indexing::Chars);
+            assert_eq!(
+                lints[0].to_string(),
+                "line 1 [positional-xor] suspicious: position 9 is past the end of a 3-character password"
+            );
+
+            // with neither position in the password, nothing can match
+            for dump in ["0-7 a: abc", "4-5 a: abc"] {
+                let lints = lint(dump, &PositionalXor, Indexing::Chars);
+                assert_eq!(lints.len(), 2, "{}", dump);
+                assert!(lints.iter().all(|l| l.severity == Severity::Unsatisfiable));
+            }
+
+            let lints = lint("1-2 a: aé", &PositionalXor, Indexing::Bytes);
+            assert_eq!(lints.len(), 1);
+            assert_eq!(lints[0].severity, Severity::Suspicious);
+            assert!(lint("1-2 a: aé", &PositionalXor, Indexing::Chars).is_empty());
+        }
+
+        #[test]
+        fn test_by_name() {
+            for policy in POLICIES {
+                assert_eq!(by_name(policy.name()).unwrap().name(), policy.name());
+            }
+            assert!(by_name("no-such-policy").is_none());
+        }
     }
 }
 
-mod part_two {
-    #[derive(PartialEq, Debug)]
-    pub struct PasswordPolicy {
-        byte: u8,
-        positions: [usize; 2],
+pub mod generate {
+    use crate::policy::{Indexing, Policy, Rule, LETTERS};
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    /// A seeded source of password dump lines whose outcome under a policy is known up front.
+    ///
+    /// The same seed always produces the same lines.
+    pub struct Generator {
+        rng: StdRng,
+        indexing: Indexing,
     }
 
-    impl PasswordPolicy {
-        fn is_valid(&self, password: &str) -> bool {
-            self.positions
-                .iter()
-                .copied()
-                .filter(|&index| password.as_bytes()[index] == self.byte)
-                .count()
-                == 1
+    impl Generator {
+        pub fn new(seed: u64) -> Self {
+            Generator {
+                rng: StdRng::seed_from_u64(seed),
+                indexing: Indexing::Chars,
+            }
         }
-    }
 
-    fn parse_line(s: &str) -> anyhow::Result<(PasswordPolicy, &str)> {
-        /*
-            Adjusted to find positions rather than range,
-                as well as account for 1-indexed char positions
-        */
-        peg::parser! {
-            grammar parser() for str {
-                rule number() -> usize
-                 = n:$(['0'..='9']+) { n.parse().unwrap() }
+        /// Generate for dumps that will be checked with `indexing` rather than the default
+        /// [`Indexing::Chars`]. A dump line doesn't say, so the whole dump shares one.
+        pub fn indexing(self, indexing: Indexing) -> Self {
+            Self { indexing, ..self }
+        }
 
-                rule position() -> usize
-                 = n:number() { n - 1 }
+        /// A random, sensible rule: `first` between 1 and 8, `second` no smaller and at most 12.
+        pub fn rule(&mut self) -> Rule {
+            let first = self.rng.gen_range(1..=8);
+            Rule {
+                first,
+                second: self.rng.gen_range(first..=12),
+                letter: *LETTERS.choose(&mut self.rng).unwrap(),
+                indexing: self.indexing,
+            }
+        }
 
-                rule positions() -> [usize; 2]
-                 = first:position() "-" second:position() { [first, second] }
+        /// A password that is guaranteed to pass `policy` under `rule`, or to fail it when
+        /// `valid` is false. `None` if the policy couldn't come up with one.
+        pub fn password(
+            &mut self,
+            policy: &dyn Policy,
+            rule: &Rule,
+            valid: bool,
+        ) -> Option<String> {
+            let password = policy.example(rule, valid, &mut self.rng)?;
+            // don't take the policy's word for it
+            (policy.is_valid(rule, &password) == valid).then_some(password)
+        }
 
-                rule byte() -> u8
-                 = letter:$(['a'..='z']) { letter.as_bytes()[0] }
+        /// A full dump line, e.g. `1-3 a: abcde`, that passes `policy` (or fails it).
+        pub fn line(&mut self, policy: &dyn Policy, valid: bool) -> Option<String> {
+            (0..100).find_map(|_| {
+                let rule = self.rule();
+                let password = self.password(policy, &rule, valid)?;
+                Some(format!("{}: {}", rule, password))
+            })
+        }
 
-                rule password() -> &'input str
-                 = letters:$([_]*) { letters }
+        /// A dump of `lines` lines, mixing valid and invalid ones, along with how many are valid.
+        pub fn dump(&mut self, policy: &dyn Policy, lines: usize) -> Option<(String, usize)> {
+            let mut dump = String::new();
+            let mut valid = 0;
+            for _ in 0..lines {
+                let is_valid = self.rng.gen_bool(0.5);
+                dump += &self.line(policy, is_valid)?;
+                dump.push('\n');
+                valid += is_valid as usize;
+            }
+            Some((dump, valid))
+        }
+    }
 
-                pub(crate) rule line() -> (PasswordPolicy, &'input str)
-                 = positions:positions() " " byte:byte() ": " password:password() {
-                    (PasswordPolicy { positions, byte }, password)
-                 }
+    #[cfg(test)]
+    mod generate_tests {
+        use super::*;
+        use crate::policy::{self, Indexing, POLICIES};
+        use proptest::prelude::*;
+
+        #[test]
+        fn test_seeded() {
+            let policy = POLICIES[0];
+            assert_eq!(
+                Generator::new(7).dump(policy, 20),
+                Generator::new(7).dump(policy, 20)
+            );
+        }
+
+        fn indexing(bytes: bool) -> Indexing {
+            match bytes {
+                true => Indexing::Bytes,
+                false => Indexing::Chars,
             }
         }
 
-        Ok(parser::line(s)?)
+        proptest! {
+            #[test]
+            fn generated_lines_round_trip(seed: u64, which in 0..POLICIES.len(), valid: bool, bytes: bool) {
+                let (policy, indexing) = (POLICIES[which], indexing(bytes));
+                let line = Generator::new(seed).indexing(indexing).line(policy, valid).unwrap();
+                prop_assert_eq!(policy::run(&line, policy, indexing), valid as usize, "{}", line);
+            }
+
+            #[test]
+            fn generated_dumps_round_trip(seed: u64, which in 0..POLICIES.len(), lines in 0..50usize, bytes: bool) {
+                let (policy, indexing) = (POLICIES[which], indexing(bytes));
+                let (dump, valid) = Generator::new(seed).indexing(indexing).dump(policy, lines).unwrap();
+                prop_assert_eq!(policy::run(&dump, policy, indexing), valid);
+                prop_assert_eq!(policy::report(&dump, policy, indexing).findings.len(), lines - valid);
+            }
+        }
     }
+}
+
+mod part_one {
+    use crate::policy::{self, CountInRange, Indexing};
 
     pub fn run(s: &str) -> usize {
-        s.lines()
-            .map(parse_line)
-            .map(Result::unwrap)
-            .filter(|(policy, password)| policy.is_valid(password))
-            .count()
+        policy::run(s, &CountInRange, Indexing::Chars)
     }
 
     #[cfg(test)]
-    mod part_two_tests {
+    mod part_one_tests {
         use super::*;
+        use crate::generate::Generator;
+        use crate::policy::{Policy, Rule};
 
         #[test]
         fn test_is_valid() {
-            let pp = PasswordPolicy {
-                positions: [0, 2],
-                byte: b'a',
+            let rule = Rule {
+                first: 1,
+                second: 3,
+                letter: 'a',
+                indexing: Indexing::Chars,
             };
-            assert_eq!(pp.is_valid("abcde"), true, "'a' in position 1");
-            assert_eq!(pp.is_valid("bcade"), true, "'a' in position 3");
-            assert_eq!(pp.is_valid("food"), false, "no 'a' whatsoever");
-            assert_eq!(pp.is_valid("abacus"), false, "'a' in both positions");
+            let pp = CountInRange;
+            assert_eq!(pp.is_valid(&rule, "zeus"), false, "no 'a's");
+            assert_eq!(pp.is_valid(&rule, "hades"), true, "single 'a'");
+            assert_eq!(pp.is_valid(&rule, "banana"), true, "three 'a's");
+            assert_eq!(pp.is_valid(&rule, "aaaah"), false, "too many 'a's");
+        }
+
+        proptest::proptest! {
+            #[test]
+            fn test_generated(seed: u64, lines in 0..50usize) {
+                let (dump, valid) = Generator::new(seed).dump(&CountInRange, lines).unwrap();
+                proptest::prop_assert_eq!(run(&dump), valid);
+            }
         }
+    }
+}
+
+mod part_two {
+    use crate::policy::{self, Indexing, PositionalXor};
+
+    pub fn run(s: &str) -> usize {
+        policy::run(s, &PositionalXor, Indexing::Chars)
+    }
+
+    #[cfg(test)]
+    mod part_two_tests {
+        use super::*;
+        use crate::generate::Generator;
+        use crate::policy::{Policy, Rule};
 
         #[test]
-        fn test_parse() {
-            assert_eq!(
-                parse_line("1-3 a: banana").unwrap(),
-                (
-                    PasswordPolicy {
-                        positions: [0, 2],
-                        byte: b'a'
-                    },
-                    "banana"
-                )
-            );
+        fn test_is_valid() {
+            let rule = Rule {
+                first: 1,
+                second: 3,
+                letter: 'a',
+                indexing: Indexing::Chars,
+            };
+            let pp = PositionalXor;
+            assert_eq!(pp.is_valid(&rule, "abcde"), true, "'a' in position 1");
+            assert_eq!(pp.is_valid(&rule, "bcade"), true, "'a' in position 3");
+            assert_eq!(pp.is_valid(&rule, "food"), false, "no 'a' whatsoever");
+            assert_eq!(pp.is_valid(&rule, "abacus"), false, "'a' in both positions");
+        }
+
+        proptest::proptest! {
+            #[test]
+            fn test_generated(seed: u64, lines in 0..50usize) {
+                let (dump, valid) = Generator::new(seed).dump(&PositionalXor, lines).unwrap();
+                proptest::prop_assert_eq!(run(&dump), valid);
+            }
         }
     }
 }
@@ -188,13 +1069,57 @@ mod part_two {
 // }
 
 fn main() -> anyhow::Result<()> {
-    // part one
-    let count = part_one::run(include_str!("input.txt"));
-    println!("{} passwords are valid", count);
+    // policies to audit by name, e.g. `cargo run -- count-in-range forbidden`,
+    // plus `--report` or `--json` to list every failing line instead of a count, `--lint` to
+    // list rules that look wrong, and `--bytes` to index passwords by byte instead of by char
+    let (flags, names): (Vec<String>, Vec<String>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    let report = flags.iter().any(|flag| flag == "--report");
+    let json = flags.iter().any(|flag| flag == "--json");
+    let lint = flags.iter().any(|flag| flag == "--lint");
+    let indexing = match flags.iter().any(|flag| flag == "--bytes") {
+        true => policy::Indexing::Bytes,
+        false => policy::Indexing::Chars,
+    };
+    if names.is_empty() {
+        // part one
+        let count = part_one::run(include_str!("input.txt"));
+        println!("{} passwords are valid", count);
 
-    // part two
-    let count = part_one::run(include_str!("input.txt"));
-    println!("{} passwords are valid", count);
+        // part two
+        let count = part_two::run(include_str!("input.txt"));
+        println!("{} passwords are valid", count);
+
+        return Ok(());
+    }
+
+    for name in names {
+        let policy = policy::by_name(&name).ok_or_else(|| {
+            let known: Vec<_> = policy::POLICIES.iter().map(|p| p.name()).collect();
+            anyhow::anyhow!("unknown policy {:?}, expected one of {:?}", name, known)
+        })?;
+        let input = include_str!("input.txt");
+        if lint {
+            for found in policy::lint(input, policy, indexing) {
+                match json {
+                    true => println!("{}", serde_json::to_string(&found)?),
+                    false => println!("{}", found),
+                }
+            }
+        }
+        if json {
+            print!(
+                "{}",
+                policy::report(input, policy, indexing).to_json_lines()
+            );
+        } else if report {
+            print!("{}", policy::report(input, policy, indexing));
+        } else {
+            let count = policy::run(input, policy, indexing);
+            println!("{}: {} passwords are valid", name, count);
+        }
+    }
 
     Ok(())
 }
@@ -1,6 +1,13 @@
-use std::{fmt::Debug, ops::AddAssign};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fmt::Debug,
+    io::Write,
+    ops::AddAssign,
+    ops::RangeInclusive,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Vec2 {
     x: i64,
     y: i64,
@@ -19,18 +26,13 @@ impl AddAssign for Vec2 {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Default)]
 enum Tile {
+    #[default]
     Open,
     Tree,
 }
 
-impl Default for Tile {
-    fn default() -> Self {
-        Tile::Open
-    }
-}
-
 impl std::fmt::Debug for Tile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let c = match self {
@@ -41,9 +43,102 @@ impl std::fmt::Debug for Tile {
     }
 }
 
+/// What happens to a position that runs off one side of the map, per axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    /// Come back in on the opposite side, like a torus.
+    Wrap,
+    /// Stick to the last row or column.
+    Clamp,
+    /// Bounce back the way it came, mirroring the map.
+    Reflect,
+    /// Fall off: there's nothing out there.
+    Stop,
+}
+
+impl Edge {
+    /// Bring coordinate `v` onto an axis of `len` cells, or `None` if it's fallen off.
+    fn apply(self, v: i64, len: i64) -> Option<i64> {
+        match self {
+            Edge::Wrap => Some(v.rem_euclid(len)),
+            Edge::Clamp => Some(v.clamp(0, len - 1)),
+            Edge::Reflect => {
+                let v = v.rem_euclid(2 * len);
+                Some(if v < len { v } else { 2 * len - 1 - v })
+            }
+            Edge::Stop => (0..len).contains(&v).then_some(v),
+        }
+    }
+
+    /// Everything about coordinate `v` that decides where a constant slope goes next, starting
+    /// from inside the map. Two positions with the same state have the same future.
+    fn state(self, v: i64, len: i64) -> i64 {
+        match self {
+            Edge::Wrap => v.rem_euclid(len),
+            // once past an edge a constant slope never comes back
+            Edge::Clamp => v.clamp(0, len - 1),
+            // which way we're heading matters, so remember a there-and-back
+            Edge::Reflect => v.rem_euclid(2 * len),
+            Edge::Stop => v,
+        }
+    }
+}
+
+impl std::str::FromStr for Edge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(Edge::Wrap),
+            "clamp" => Ok(Edge::Clamp),
+            "reflect" => Ok(Edge::Reflect),
+            "stop" => Ok(Edge::Stop),
+            _ => Err(format!(
+                "unknown edge {:?}, expected wrap, clamp, reflect or stop",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edges {
+    x: Edge,
+    y: Edge,
+}
+
+/// The puzzle's map repeats to the right but ends at the bottom.
+impl Default for Edges {
+    fn default() -> Self {
+        Self {
+            x: Edge::Wrap,
+            y: Edge::Stop,
+        }
+    }
+}
+
+/// `"wrap"` for both axes, or `"wrap,stop"` for `x` then `y`.
+impl std::str::FromStr for Edges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(',') {
+            Some((x, y)) => Ok(Self {
+                x: x.parse()?,
+                y: y.parse()?,
+            }),
+            None => {
+                let edge = s.parse()?;
+                Ok(Self { x: edge, y: edge })
+            }
+        }
+    }
+}
+
 struct Map {
     size: Vec2,
     tiles: Vec<Tile>,
+    edges: Edges,
 }
 
 impl Map {
@@ -51,13 +146,15 @@ impl Map {
         let num_tiles = size.x * size.y;
         Self {
             size,
-            tiles: (0..num_tiles)
-                .into_iter()
-                .map(|_| Default::default())
-                .collect(),
+            tiles: (0..num_tiles).map(|_| Default::default()).collect(),
+            edges: Default::default(),
         }
     }
 
+    fn with_edges(self, edges: Edges) -> Self {
+        Self { edges, ..self }
+    }
+
     fn set(&mut self, pos: Vec2, tile: Tile) {
         if let Some(index) = self.index(pos) {
             self.tiles[index] = tile
@@ -68,17 +165,11 @@ impl Map {
         self.index(pos).map(|i| self.tiles[i]).unwrap_or_default()
     }
 
+    /// Bring `pos` onto the map according to its [`Edges`], or `None` if it's fallen off.
     fn normalize_pos(&self, pos: Vec2) -> Option<Vec2> {
-        if pos.y < 0 || pos.y >= self.size.y {
-            // return None if off the top or bottom
-            None
-        } else {
-            // i should revisit how/why this works with negatives, but it's passing nicely
-            let x = pos.x % self.size.x;
-            // wrap around positions for the left side
-            let x = if x < 0 { self.size.x + x } else { x };
-            Some((x, pos.y).into())
-        }
+        let x = self.edges.x.apply(pos.x, self.size.x)?;
+        let y = self.edges.y.apply(pos.y, self.size.y)?;
+        Some((x, y).into())
     }
 
     fn index(&self, pos: Vec2) -> Option<usize> {
@@ -86,6 +177,12 @@ impl Map {
             .map(|pos| (pos.x + pos.y * self.size.x) as _)
     }
 
+    // inverse of `index`
+    fn position(&self, index: usize) -> Vec2 {
+        let index = index as i64;
+        (index % self.size.x, index / self.size.x).into()
+    }
+
     fn parse(input: &[u8]) -> Self {
         let mut columns = 0;
         let mut rows = 1;
@@ -113,13 +210,242 @@ impl Map {
         }
         map
     }
+
+    fn count_trees(&self, delta: Vec2) -> usize {
+        generate_itinerary(self, delta)
+            .into_iter()
+            .filter(|&pos| self.get(pos) == Tile::Tree)
+            .count()
+    }
+
+    /// Count the trees on every slope with `x` in `xs` and `y` in `ys`, fewest trees first.
+    /// Equal counts keep the order the slopes were tried in, `x` then `y`.
+    ///
+    /// Slopes that never move down (`y <= 0`) don't reach the bottom of a map that stops there,
+    /// so they're skipped.
+    fn rank_slopes(&self, xs: RangeInclusive<i64>, ys: RangeInclusive<i64>) -> Vec<SlopeScore> {
+        let mut scores: Vec<SlopeScore> = xs
+            .flat_map(|x| ys.clone().map(move |y| Vec2::from((x, y))))
+            .filter(|slope| slope.y > 0 || self.edges.y != Edge::Stop)
+            .map(|slope| SlopeScore {
+                slope,
+                trees: self.count_trees(slope),
+            })
+            .collect();
+        scores.sort_by_key(|score| score.trees);
+        scores
+    }
 }
 
-impl Debug for Map {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SlopeScore {
+    slope: Vec2,
+    trees: usize,
+}
+
+/// How a [`Trajectory`] met a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Contact {
+    /// One of the points the slope samples, as in `generate_itinerary`.
+    Landed,
+    /// Crossed on the straight line between two landings.
+    Passed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Trajectory {
+    /// Every cell the line goes through, in order, before wrapping onto the map.
+    cells: Vec<(Vec2, Contact)>,
+    /// As in [`Itinerary`]: if the line never leaves the map, `cells[start..]` repeats forever.
+    cycle_start: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct TreeTally {
+    landed: usize,
+    passed: usize,
+}
+
+/// The cells strictly between `from` and `from + delta` on the straight line joining their
+/// centres. Where the line goes exactly through a corner it steps diagonally, without touching
+/// the two cells on either side.
+fn cells_between(from: Vec2, delta: Vec2) -> Vec<Vec2> {
+    let (nx, ny) = (delta.x.abs(), delta.y.abs());
+    let step = Vec2::from((delta.x.signum(), delta.y.signum()));
+    let (mut ix, mut iy) = (0, 0);
+    let mut pos = from;
+    let mut cells = vec![];
+
+    while ix < nx || iy < ny {
+        // the line crosses its `i`th vertical cell border at t = (2i + 1) / 2nx, and likewise
+        // for horizontal ones; cross-multiply to compare them without fractions
+        match ((2 * ix + 1) * ny).cmp(&((2 * iy + 1) * nx)) {
+            Ordering::Less => {
+                pos.x += step.x;
+                ix += 1;
+            }
+            Ordering::Greater => {
+                pos.y += step.y;
+                iy += 1;
+            }
+            Ordering::Equal => {
+                pos += step;
+                ix += 1;
+                iy += 1;
+            }
+        }
+        cells.push(pos);
+    }
+
+    // the last one is `from + delta`
+    cells.pop();
+    cells
+}
+
+impl Map {
+    /// Follow the straight line through the landings of `delta` (see [`trace`]), so a slope
+    /// of 3 right for every 7 down is `(3, 7)`: it lands once every 7 rows and crosses
+    /// everything in between. Stops at the first cell that falls off the map.
+    fn trajectory(&self, delta: Vec2) -> Trajectory {
+        let landings = trace(self, delta);
+        let mut cells = vec![];
+        let mut cycle_start = None;
+
+        for (i, &landing) in landings.positions.iter().enumerate() {
+            if landings.cycle_start == Some(i) {
+                cycle_start = Some(cells.len());
+            }
+            cells.push((landing, Contact::Landed));
+            for pos in cells_between(landing, delta) {
+                if self.normalize_pos(pos).is_none() {
+                    return Trajectory { cells, cycle_start };
+                }
+                cells.push((pos, Contact::Passed));
+            }
+        }
+
+        Trajectory { cells, cycle_start }
+    }
+
+    /// Trees on the line for `delta`, split by whether they were landed on or passed over.
+    /// Each cell of a loop is counted once.
+    fn count_trees_crossed(&self, delta: Vec2) -> TreeTally {
+        let mut tally = TreeTally::default();
+        for (pos, contact) in self.trajectory(delta).cells {
+            match (self.get(pos), contact) {
+                (Tile::Open, _) => {}
+                (Tile::Tree, Contact::Landed) => tally.landed += 1,
+                (Tile::Tree, Contact::Passed) => tally.passed += 1,
+            }
+        }
+        tally
+    }
+}
+
+/// `"3/7"` for 3 right for every 7 down, or just `"3"` for 3 right for every 1 down.
+fn parse_slope(s: &str) -> Result<Vec2, std::num::ParseIntError> {
+    let (right, down) = s.split_once('/').unwrap_or((s, "1"));
+    Ok((right.trim().parse()?, down.trim().parse()?).into())
+}
+
+/// How [`Map::shortest_route`] treats trees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TreeCost {
+    /// Never step onto a tree.
+    Impassable,
+    /// Stepping onto a tree is allowed, but costs this much on top of the move itself.
+    Weighted(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Route {
+    /// Every cell from the top row to the bottom row, with `x` wrapped onto the map.
+    path: Vec<Vec2>,
+    cost: u64,
+}
+
+impl Map {
+    fn entry_cost(&self, pos: Vec2, trees: TreeCost) -> Option<u64> {
+        match (self.get(pos), trees) {
+            (Tile::Open, _) => Some(0),
+            (Tile::Tree, TreeCost::Impassable) => None,
+            (Tile::Tree, TreeCost::Weighted(weight)) => Some(weight),
+        }
+    }
+
+    /// The cheapest way from any cell in the top row to any cell in the bottom row, taking one
+    /// of `moves` at a time. Running off an edge does whatever the map's [`Edges`] say.
+    ///
+    /// Every move costs 1, and landing on a tree (including the starting cell) adds whatever
+    /// `trees` says. Returns `None` if the bottom can't be reached.
+    fn shortest_route(&self, moves: &[Vec2], trees: TreeCost) -> Option<Route> {
+        let mut best = vec![u64::MAX; self.tiles.len()];
+        let mut came_from: Vec<Option<usize>> = vec![None; self.tiles.len()];
+        let mut queue = BinaryHeap::new();
+
+        for x in 0..self.size.x {
+            let pos = Vec2::from((x, 0));
+            if let (Some(index), Some(cost)) = (self.index(pos), self.entry_cost(pos, trees)) {
+                best[index] = cost;
+                queue.push(Reverse((cost, index)));
+            }
+        }
+
+        while let Some(Reverse((cost, index))) = queue.pop() {
+            if cost > best[index] {
+                continue;
+            }
+
+            let pos = self.position(index);
+            if pos.y == self.size.y - 1 {
+                let mut path = vec![pos];
+                let mut index = index;
+                while let Some(previous) = came_from[index] {
+                    path.push(self.position(previous));
+                    index = previous;
+                }
+                path.reverse();
+                return Some(Route { path, cost });
+            }
+
+            for &step in moves {
+                let mut next = pos;
+                next += step;
+                let (next, extra) = match (self.index(next), self.entry_cost(next, trees)) {
+                    (Some(next), Some(extra)) => (next, extra),
+                    _ => continue,
+                };
+                let next_cost = cost + 1 + extra;
+                if next_cost < best[next] {
+                    best[next] = next_cost;
+                    came_from[next] = Some(index);
+                    queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// A view of the map whose `Debug` output marks `path` on it, using the puzzle's notation:
+    /// `O` for an open cell on the path and `X` for a tree on it.
+    fn overlay<'a>(&'a self, path: &'a [Vec2]) -> Overlay<'a> {
+        Overlay { map: self, path }
+    }
+
+    fn fmt_tiles(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        marked: &HashSet<Vec2>,
+    ) -> std::fmt::Result {
         for row in 0..self.size.y {
             for col in 0..self.size.x {
-                write!(f, "{:?}", self.get((col, row).into()))?;
+                let pos = Vec2::from((col, row));
+                write!(
+                    f,
+                    "{}",
+                    Cell::new(self.get(pos), marked.contains(&pos)).text()
+                )?;
             }
             writeln!(f)?;
         }
@@ -127,9 +453,158 @@ impl Debug for Map {
     }
 }
 
+struct Overlay<'a> {
+    map: &'a Map,
+    path: &'a [Vec2],
+}
+
+impl Debug for Overlay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let marked = self
+            .path
+            .iter()
+            .filter_map(|&pos| self.map.normalize_pos(pos))
+            .collect();
+        self.map.fmt_tiles(f, &marked)
+    }
+}
+
+/// Output formats for [`Map::render`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenderFormat {
+    /// The puzzle's notation: `.` and `#`, with `O` and `X` where the itinerary lands.
+    Text,
+    /// The same characters, coloured with ANSI escapes for a terminal.
+    Ansi,
+    /// A binary PPM image with `scale` by `scale` pixels per cell.
+    Ppm { scale: usize },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+    Open,
+    Tree,
+    Visited,
+    Hit,
+}
+
+impl Cell {
+    fn new(tile: Tile, marked: bool) -> Self {
+        match (marked, tile) {
+            (false, Tile::Open) => Cell::Open,
+            (false, Tile::Tree) => Cell::Tree,
+            (true, Tile::Open) => Cell::Visited,
+            (true, Tile::Tree) => Cell::Hit,
+        }
+    }
+
+    fn text(self) -> char {
+        match self {
+            Cell::Open => '.',
+            Cell::Tree => '#',
+            Cell::Visited => 'O',
+            Cell::Hit => 'X',
+        }
+    }
+
+    fn ansi(self) -> &'static str {
+        match self {
+            Cell::Open => "\x1b[2m.\x1b[0m",
+            Cell::Tree => "\x1b[32m#\x1b[0m",
+            Cell::Visited => "\x1b[1;36mO\x1b[0m",
+            Cell::Hit => "\x1b[1;31mX\x1b[0m",
+        }
+    }
+
+    fn rgb(self) -> [u8; 3] {
+        match self {
+            Cell::Open => [0xf4, 0xf4, 0xf4],
+            Cell::Tree => [0x1d, 0x6b, 0x2f],
+            Cell::Visited => [0x2f, 0x8f, 0xe0],
+            Cell::Hit => [0xe0, 0x2f, 0x2f],
+        }
+    }
+}
+
+impl Map {
+    /// Render the map repeated as many times as it takes to show all of `itinerary` without
+    /// wrapping, marking every cell it lands on. Copies beyond an edge show whatever `get` finds
+    /// there.
+    fn render(
+        &self,
+        itinerary: &[Vec2],
+        format: RenderFormat,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        // whole copies of the map, so the tiling lines up with the original
+        let span = |coords: &mut dyn Iterator<Item = i64>, len: i64| {
+            let first = coords.next().unwrap_or(0);
+            let (min, max) = coords.fold((first, first), |(min, max), v| (v.min(min), v.max(max)));
+            (min.div_euclid(len) * len, (max.div_euclid(len) + 1) * len)
+        };
+        let (left, right) = span(&mut itinerary.iter().map(|pos| pos.x), self.size.x);
+        let (top, bottom) = span(&mut itinerary.iter().map(|pos| pos.y), self.size.y);
+
+        let visited: HashSet<Vec2> = itinerary.iter().copied().collect();
+        let rows: Vec<Vec<Cell>> = (top..bottom)
+            .map(|y| {
+                (left..right)
+                    .map(|x| {
+                        let pos = Vec2::from((x, y));
+                        Cell::new(self.get(pos), visited.contains(&pos))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        match format {
+            RenderFormat::Text => {
+                for row in rows {
+                    writeln!(
+                        out,
+                        "{}",
+                        row.into_iter().map(Cell::text).collect::<String>()
+                    )?;
+                }
+            }
+            RenderFormat::Ansi => {
+                for row in rows {
+                    writeln!(
+                        out,
+                        "{}",
+                        row.into_iter().map(Cell::ansi).collect::<String>()
+                    )?;
+                }
+            }
+            RenderFormat::Ppm { scale } => {
+                let width = (right - left) as usize * scale;
+                let height = (bottom - top) as usize * scale;
+                write!(out, "P6\n{} {}\n255\n", width, height)?;
+                for row in rows {
+                    let line: Vec<u8> = row
+                        .into_iter()
+                        .flat_map(|cell| std::iter::repeat_n(cell.rgb(), scale))
+                        .flatten()
+                        .collect();
+                    for _ in 0..scale {
+                        out.write_all(&line)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_tiles(f, &HashSet::new())
+    }
+}
+
 fn part_one_main(bytes: &[u8]) -> usize {
     let map = Map::parse(bytes);
-    let itinerary = (0..map.size.y).into_iter().map(|y| Vec2::from((y * 3, y)));
+    let itinerary = (0..map.size.y).map(|y| Vec2::from((y * 3, y)));
     itinerary.filter(|&pos| map.get(pos) == Tile::Tree).count()
 }
 
@@ -148,30 +623,61 @@ fn part_two_main(bytes: &[u8]) -> usize {
     deltas
         .iter()
         .copied()
-        .map(|delta| generate_itinerary(&map, delta))
-        .map(|itinerary| {
-            itinerary
-                .into_iter()
-                .filter(|&pos| map.get(pos) == Tile::Tree)
-                .count()
-        })
+        .map(|delta| map.count_trees(delta))
         .product::<usize>()
 }
 
 fn generate_itinerary(map: &Map, delta: Vec2) -> Vec<Vec2> {
+    trace(map, delta).positions
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Itinerary {
+    /// Every position landed on, before wrapping onto the map.
+    positions: Vec<Vec2>,
+    /// If the itinerary never falls off the map, `positions[start..]` repeats forever.
+    cycle_start: Option<usize>,
+}
+
+impl Itinerary {
+    /// One trip around the loop, if there is one.
+    fn lap(&self) -> Option<&[Vec2]> {
+        self.cycle_start.map(|start| &self.positions[start..])
+    }
+}
+
+/// Follow `delta` from the top left until it falls off the map or comes back to a state it's
+/// been in before, which on a fully wrapped map means revisiting a cell.
+fn trace(map: &Map, delta: Vec2) -> Itinerary {
     let mut pos = Vec2::from((0, 0));
-    let mut res: Vec<_> = Default::default();
+    let mut positions: Vec<_> = Default::default();
+    let mut seen: HashMap<(i64, i64), usize> = Default::default();
 
     while map.normalize_pos(pos).is_some() {
-        res.push(pos);
+        let state = (
+            map.edges.x.state(pos.x, map.size.x),
+            map.edges.y.state(pos.y, map.size.y),
+        );
+        if let Some(&start) = seen.get(&state) {
+            return Itinerary {
+                positions,
+                cycle_start: Some(start),
+            };
+        }
+        seen.insert(state, positions.len());
+        positions.push(pos);
         pos += delta;
     }
 
-    res
+    Itinerary {
+        positions,
+        cycle_start: None,
+    }
 }
 
-fn main() {
+fn main() -> std::io::Result<()> {
     let bytes = include_bytes!("input.txt");
+    let args: Vec<String> = std::env::args().collect();
 
     // part one
     let count = part_one_main(bytes);
@@ -180,6 +686,120 @@ fn main() {
     // part two
     let count = part_two_main(bytes);
     println!("we encountered {} trees", count);
+
+    // every slope up to 7 across and 2 down, either direction
+    let ranked = Map::parse(bytes).rank_slopes(-7..=7, 1..=2);
+    if let (Some(safest), Some(riskiest)) = (ranked.first(), ranked.last()) {
+        println!("safest slope is {:?}", safest);
+        println!("riskiest slope is {:?}", riskiest);
+    }
+
+    // `--edges wrap` or `--edges wrap,reflect` changes what lies beyond the sides of the map
+    let edges = match args.iter().position(|arg| arg == "--edges") {
+        Some(i) => match args.get(i + 1).map(|edges| edges.parse()) {
+            Some(Ok(edges)) => edges,
+            Some(Err(e)) => {
+                eprintln!("bad --edges: {}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("--edges needs a value like `wrap,stop`");
+                std::process::exit(1);
+            }
+        },
+        None => Edges::default(),
+    };
+    let map = Map::parse(bytes).with_edges(edges);
+
+    let course = trace(&map, (3, 1).into());
+    match course.lap() {
+        Some(lap) => println!(
+            "{:?}: slope (3, 1) loops every {} cells, hitting {} trees per lap",
+            edges,
+            lap.len(),
+            lap.iter()
+                .filter(|&&pos| map.get(pos) == Tile::Tree)
+                .count()
+        ),
+        None => println!(
+            "{:?}: slope (3, 1) leaves the map after {} cells",
+            edges,
+            course.positions.len()
+        ),
+    }
+
+    // every cell the line crosses, not just the ones it lands on: `--slope 3/7`
+    let slopes = match args.iter().position(|arg| arg == "--slope") {
+        Some(i) => match args.get(i + 1).map(|slope| parse_slope(slope)) {
+            Some(Ok(slope)) => vec![slope],
+            Some(Err(e)) => {
+                eprintln!("bad --slope: {}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("--slope needs a value like `3/7`");
+                std::process::exit(1);
+            }
+        },
+        None => vec![(3, 1).into(), (1, 2).into()],
+    };
+    for delta in slopes {
+        let tally = map.count_trees_crossed(delta);
+        println!(
+            "slope {}/{} lands on {} trees and passes over {} more",
+            delta.x, delta.y, tally.landed, tally.passed
+        );
+    }
+
+    // weaving down the slope instead of following a straight line
+    let moves: &[Vec2] = &[(0, 1).into(), (-1, 1).into(), (1, 1).into()];
+    for trees in [TreeCost::Impassable, TreeCost::Weighted(10)] {
+        match map.shortest_route(moves, trees) {
+            Some(route) => {
+                println!("{:?}: best route costs {}", trees, route.cost);
+                if args.iter().any(|arg| arg == "--show-route") {
+                    println!("{:?}", map.overlay(&route.path));
+                }
+            }
+            None => println!("{:?}: no route to the bottom", trees),
+        }
+    }
+
+    // draw the part one trajectory: `--render text`, `--render ansi` or `--render ppm <file>`
+    if let Some(i) = args.iter().position(|arg| arg == "--render") {
+        let itinerary = generate_itinerary(&map, (3, 1).into());
+        let path = args.get(i + 2).filter(|path| !path.starts_with("--"));
+        match (args.get(i + 1).map(String::as_str), path) {
+            (Some("ppm"), Some(path)) => {
+                let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                map.render(&itinerary, RenderFormat::Ppm { scale: 4 }, &mut file)?;
+                println!("wrote {}", path);
+            }
+            (Some("ppm"), None) => {
+                eprintln!("--render ppm needs a file to write to");
+                std::process::exit(1);
+            }
+            (Some("ansi"), _) => map.render(
+                &itinerary,
+                RenderFormat::Ansi,
+                &mut std::io::stdout().lock(),
+            )?,
+            (Some("text"), _) => map.render(
+                &itinerary,
+                RenderFormat::Text,
+                &mut std::io::stdout().lock(),
+            )?,
+            (format, _) => {
+                eprintln!(
+                    "--render takes text, ansi or ppm <file>, not {:?}",
+                    format.unwrap_or_default()
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -241,6 +861,165 @@ mod tests {
         assert_eq!(m.normalize_pos((0, 2).into()), None);
     }
 
+    #[test]
+    fn test_edges() {
+        let m = |x: Edge, y: Edge| Map::new((3, 2).into()).with_edges(Edges { x, y });
+
+        let clamp = m(Edge::Clamp, Edge::Clamp);
+        assert_eq!(clamp.normalize_pos((-4, 0).into()), Some((0, 0).into()));
+        assert_eq!(clamp.normalize_pos((7, 9).into()), Some((2, 1).into()));
+
+        let reflect = m(Edge::Reflect, Edge::Reflect);
+        let xs: Vec<_> = (-3..=6)
+            .map(|x| reflect.normalize_pos((x, 0).into()).unwrap().x)
+            .collect();
+        assert_eq!(xs, [2, 1, 0, 0, 1, 2, 2, 1, 0, 0]);
+        assert_eq!(reflect.normalize_pos((0, -1).into()), Some((0, 0).into()));
+
+        let torus = m(Edge::Wrap, Edge::Wrap);
+        assert_eq!(torus.normalize_pos((-1, -1).into()), Some((2, 1).into()));
+        assert_eq!(torus.normalize_pos((3, 5).into()), Some((0, 1).into()));
+
+        let stop = m(Edge::Stop, Edge::Wrap);
+        assert_eq!(stop.normalize_pos((3, 0).into()), None);
+        assert_eq!(stop.normalize_pos((2, 2).into()), Some((2, 0).into()));
+
+        assert_eq!("wrap".parse(), Ok(torus.edges));
+        assert_eq!("stop,wrap".parse(), Ok(stop.edges));
+        assert!("wrap,bounce".parse::<Edges>().is_err());
+    }
+
+    #[test]
+    fn test_trace_cycles() {
+        let example = || Map::parse(EXAMPLE.as_bytes());
+
+        // the puzzle's map falls off the bottom, exactly as before
+        let course = trace(&example(), (3, 1).into());
+        assert_eq!(course.cycle_start, None);
+        assert_eq!(
+            course.positions,
+            generate_itinerary(&example(), (3, 1).into())
+        );
+
+        // on a torus the same slope comes back to the start after one pass
+        let torus = example().with_edges("wrap".parse().unwrap());
+        let course = trace(&torus, (3, 1).into());
+        assert_eq!(course.cycle_start, Some(0));
+        assert_eq!(course.positions.len(), 11);
+        let trees = |lap: &[Vec2]| {
+            lap.iter()
+                .filter(|&&pos| torus.get(pos) == Tile::Tree)
+                .count()
+        };
+        assert_eq!(trees(course.lap().unwrap()), 7);
+
+        // and slopes that go up or stay level are closed loops too
+        assert_eq!(trace(&torus, (1, -1).into()).lap().unwrap().len(), 11);
+        assert_eq!(trace(&torus, (2, 0).into()).lap().unwrap().len(), 11);
+
+        // clamping ends up stuck in the corner
+        let clamp = Map::new((3, 3).into()).with_edges("clamp".parse().unwrap());
+        let course = trace(&clamp, (1, 1).into());
+        assert_eq!(course.positions.len(), 3);
+        assert_eq!(course.lap(), Some(&[Vec2::from((2, 2))][..]));
+
+        // reflecting has to go there and back before it repeats
+        let reflect = Map::new((3, 3).into()).with_edges("wrap,reflect".parse().unwrap());
+        let course = trace(&reflect, (1, 1).into());
+        assert_eq!(course.cycle_start, Some(0));
+        let ys: Vec<_> = course
+            .positions
+            .iter()
+            .map(|&pos| reflect.normalize_pos(pos).unwrap().y)
+            .collect();
+        assert_eq!(ys, [0, 1, 2, 2, 1, 0]);
+
+        // standing still used to loop forever
+        let course = trace(&Map::new((3, 3).into()), (0, 0).into());
+        assert_eq!(course.lap(), Some(&[Vec2::from((0, 0))][..]));
+    }
+
+    #[test]
+    fn test_cells_between() {
+        let between = |x, y| cells_between((0, 0).into(), (x, y).into());
+        let cells =
+            |list: &[(i64, i64)]| list.iter().map(|&pos| Vec2::from(pos)).collect::<Vec<_>>();
+
+        assert_eq!(between(1, 1), []);
+        assert_eq!(between(0, 3), cells(&[(0, 1), (0, 2)]));
+        // through the corner at (1.5, 0.5)
+        assert_eq!(between(3, 1), cells(&[(1, 0), (2, 1)]));
+        assert_eq!(between(-3, 1), cells(&[(-1, 0), (-2, 1)]));
+        assert_eq!(between(2, 1), cells(&[(1, 0), (1, 1)]));
+        assert_eq!(between(1, 3), cells(&[(0, 1), (1, 2)]));
+        assert_eq!(
+            between(6, 2),
+            cells(&[(1, 0), (2, 1), (3, 1), (4, 1), (5, 2)])
+        );
+    }
+
+    #[test]
+    fn test_trajectory() {
+        let forest = Map::parse(b"###\n###\n###");
+        let trajectory = forest.trajectory((3, 1).into());
+        let landed = |pos: (i64, i64)| (Vec2::from(pos), Contact::Landed);
+        let passed = |pos: (i64, i64)| (Vec2::from(pos), Contact::Passed);
+        assert_eq!(
+            trajectory.cells,
+            [
+                landed((0, 0)),
+                passed((1, 0)),
+                passed((2, 1)),
+                landed((3, 1)),
+                passed((4, 1)),
+                passed((5, 2)),
+                landed((6, 2)),
+                passed((7, 2)),
+            ]
+        );
+        assert_eq!(trajectory.cycle_start, None);
+        assert_eq!(
+            forest.count_trees_crossed((3, 1).into()),
+            TreeTally {
+                landed: 3,
+                passed: 5
+            }
+        );
+
+        // the landings are exactly what the sampled itinerary sees
+        let map = Map::parse(EXAMPLE.as_bytes());
+        for delta in [(3, 1), (3, 7), (-2, 3), (1, 2)] {
+            let delta = Vec2::from(delta);
+            let trajectory = map.trajectory(delta);
+            assert_eq!(
+                map.count_trees_crossed(delta).landed,
+                map.count_trees(delta),
+                "{:?}",
+                delta
+            );
+            // and the line never skips a cell
+            for pair in trajectory.cells.windows(2) {
+                let (a, b) = (pair[0].0, pair[1].0);
+                assert!((a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1);
+            }
+            assert_eq!(trajectory.cells.last().unwrap().0.y, 10);
+        }
+
+        // a loop on a torus includes the stretch back to the start
+        let torus = Map::new((3, 3).into()).with_edges("wrap".parse().unwrap());
+        let trajectory = torus.trajectory((2, 1).into());
+        assert_eq!(trajectory.cycle_start, Some(0));
+        assert_eq!(trajectory.cells.len(), 9);
+    }
+
+    #[test]
+    fn test_parse_slope() {
+        assert_eq!(parse_slope("3/7"), Ok((3, 7).into()));
+        assert_eq!(parse_slope("-1/2"), Ok((-1, 2).into()));
+        assert_eq!(parse_slope("5"), Ok((5, 1).into()));
+        assert!(parse_slope("3/x").is_err());
+    }
+
     #[test]
     fn test_index() {
         let m = Map::new((3, 5).into());
@@ -287,4 +1066,149 @@ mod tests {
             "right 2 down 5, 9x9 map"
         )
     }
+
+    #[test]
+    fn test_rank_slopes() {
+        let map = Map::parse(EXAMPLE.as_bytes());
+        let ranked = map.rank_slopes(-7..=7, 0..=2);
+
+        assert_eq!(ranked.len(), 15 * 2, "y = 0 is skipped");
+        assert!(ranked.windows(2).all(|w| w[0].trees <= w[1].trees));
+
+        let trees = |x: i64, y: i64| {
+            ranked
+                .iter()
+                .find(|score| score.slope == (x, y).into())
+                .unwrap()
+                .trees
+        };
+        // the five slopes from the prompt
+        assert_eq!(
+            [
+                trees(1, 1),
+                trees(3, 1),
+                trees(5, 1),
+                trees(7, 1),
+                trees(1, 2)
+            ],
+            [2, 7, 3, 4, 2]
+        );
+        // negative slopes agree with a plain walk, and with their wrapped twin
+        for x in -7..=-1 {
+            assert_eq!(trees(x, 1), map.count_trees((x, 1).into()));
+            assert_eq!(trees(x, 1), map.count_trees((x + map.size.x, 1).into()));
+        }
+
+        let safest = ranked.first().unwrap();
+        let riskiest = ranked.last().unwrap();
+        assert_eq!(safest.trees, map.rank_slopes(-7..=7, 1..=2)[0].trees);
+        assert!(riskiest.trees >= trees(3, 1));
+    }
+
+    #[test]
+    fn test_shortest_route() {
+        let map = Map::parse(EXAMPLE.as_bytes());
+        let moves: &[Vec2] = &[(0, 1).into(), (-1, 1).into(), (1, 1).into()];
+
+        let route = map.shortest_route(moves, TreeCost::Impassable).unwrap();
+        assert_eq!(route.path.len() as i64, map.size.y);
+        assert_eq!(route.cost, map.size.y as u64 - 1);
+        assert!(route.path.iter().all(|&pos| map.get(pos) == Tile::Open));
+        for pair in route.path.windows(2) {
+            assert_eq!(pair[1].y, pair[0].y + 1);
+            assert!(
+                (pair[1].x - pair[0].x).rem_euclid(map.size.x) <= 1
+                    || (pair[0].x - pair[1].x).rem_euclid(map.size.x) == 1
+            );
+        }
+
+        // straight down only: every column hits a tree somewhere
+        assert_eq!(
+            map.shortest_route(&[(0, 1).into()], TreeCost::Impassable),
+            None
+        );
+        let route = map
+            .shortest_route(&[(0, 1).into()], TreeCost::Weighted(100))
+            .unwrap();
+        assert_eq!(route.cost, 10 + 100);
+    }
+
+    #[test]
+    fn test_shortest_route_wraps() {
+        let map = Map::parse(b".##\n##.\n.##");
+        let moves: &[Vec2] = &[(-1, 1).into(), (1, 1).into()];
+        let route = map.shortest_route(moves, TreeCost::Impassable).unwrap();
+        assert_eq!(
+            route.path,
+            vec![(0, 0).into(), (2, 1).into(), (0, 2).into()]
+        );
+        assert_eq!(route.cost, 2);
+        assert_eq!(format!("{:?}", map.overlay(&route.path)), "O##\n##O\nO##\n");
+
+        let route = map
+            .shortest_route(&[(0, 1).into()], TreeCost::Weighted(5))
+            .unwrap();
+        assert_eq!(route.cost, 2 + 5);
+        assert_eq!(format!("{:?}", map.overlay(&route.path)), "O##\nX#.\nO##\n");
+    }
+
+    #[test]
+    fn test_render_text() {
+        let map = Map::parse(b".##\n##.\n.##");
+        let render = |itinerary: &[Vec2]| {
+            let mut out = vec![];
+            map.render(itinerary, RenderFormat::Text, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            render(&[(0, 0).into(), (2, 1).into(), (4, 2).into()]),
+            "O##.##\n##O##.\n.##.X#\n"
+        );
+        assert_eq!(
+            render(&[(0, 0).into(), (-1, 1).into()]),
+            ".##O##\n##O##.\n.##.##\n",
+            "tiles to the left for negative x"
+        );
+        assert_eq!(render(&[]), ".##\n##.\n.##\n");
+        assert_eq!(
+            render(&[(4, 1).into(), (5, 2).into()]),
+            ".##\n#X.\n.#X\n",
+            "only the tiles the itinerary reaches"
+        );
+
+        let map = Map::parse(EXAMPLE.as_bytes());
+        let mut out = vec![];
+        let itinerary = generate_itinerary(&map, (3, 1).into());
+        map.render(&itinerary, RenderFormat::Text, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 11);
+        assert!(out.lines().all(|line| line.len() == 33));
+        assert_eq!(out.matches('X').count(), 7, "same trees as part one");
+        assert_eq!(out.matches('O').count(), 11 - 7);
+    }
+
+    #[test]
+    fn test_render_ansi_and_ppm() {
+        let map = Map::parse(b".#\n#.");
+        let itinerary = [(0, 0).into(), (1, 1).into()];
+
+        let mut out = vec![];
+        map.render(&itinerary, RenderFormat::Ansi, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out.lines().next().unwrap(),
+            "\x1b[1;36mO\x1b[0m\x1b[32m#\x1b[0m"
+        );
+
+        let mut out = vec![];
+        map.render(&itinerary, RenderFormat::Ppm { scale: 2 }, &mut out)
+            .unwrap();
+        let header = b"P6\n4 4\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(out.len(), header.len() + 4 * 4 * 3);
+        assert_eq!(&out[header.len()..header.len() + 3], &Cell::Visited.rgb());
+    }
 }
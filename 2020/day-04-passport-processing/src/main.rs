@@ -1,16 +1,84 @@
 #![allow(dead_code)]
 
-use std::ops::RangeInclusive;
+use batch::OwnedPassport;
+use schema::Schema;
+use std::{collections::HashSet, ops::Range};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
 struct Year(u64);
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-enum Length {
+/// The units heights are kept in. Other notations (metres, feet...) are converted to whichever
+/// of these they belong with.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+enum Unit {
     /// Centimeters (correct)
-    Cm(u64),
+    Cm,
     /// Inches (incorrect)
-    In(u64),
+    In,
+}
+
+impl Unit {
+    fn in_cm(self) -> f64 {
+        match self {
+            Unit::Cm => 1.0,
+            Unit::In => 2.54,
+        }
+    }
+}
+
+/// A measurement, in the unit it was given in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Length {
+    value: f64,
+    unit: Unit,
+}
+
+impl Length {
+    fn new(value: f64, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+
+    /// The same length in another unit.
+    fn to(self, unit: Unit) -> Self {
+        Self::new(self.value * self.unit.in_cm() / unit.in_cm(), unit)
+    }
+}
+
+/// As written in a passport: `183cm`
+impl std::fmt::Display for Length {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.unit {
+            Unit::Cm => "cm",
+            Unit::In => "in",
+        };
+        write!(f, "{}{}", self.value, unit)
+    }
+}
+
+/// Any notation `fields::length` takes, such as `180cm`, `1.80m`, `180 cm` or `5'11"`.
+impl std::str::FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fields::length(s).map_err(|_| format!("not a length: {:?}", s))
+    }
+}
+
+impl serde::Serialize for Length {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Length {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -20,16 +88,19 @@ struct Color<'a>(&'a str);
 #[derive(Clone, Copy, PartialEq, Debug)]
 struct ID<'a>(&'a str);
 
+/// Every field is optional here: it's the schema that says which ones a passport needs.
 #[derive(PartialEq, Debug)]
 struct Passport<'a> {
-    birth_year: Year,
-    issue_year: Year,
-    expiration_year: Year,
-    height: Length,
-    hair_color: Color<'a>,
-    eye_color: Color<'a>,
-    passport_id: ID<'a>,
+    birth_year: Option<Year>,
+    issue_year: Option<Year>,
+    expiration_year: Option<Year>,
+    height: Option<Length>,
+    hair_color: Option<Color<'a>>,
+    eye_color: Option<Color<'a>>,
+    passport_id: Option<ID<'a>>,
     country_id: Option<ID<'a>>,
+    /// Fields the schema knows about that passports don't have a place for, as written
+    extra: Vec<(&'a str, &'a str)>,
 }
 
 #[derive(PartialEq, Debug, Default)]
@@ -42,153 +113,1256 @@ struct PassportBuilder<'a> {
     eye_color: Option<Color<'a>>,
     passport_id: Option<ID<'a>>,
     country_id: Option<ID<'a>>,
+    extra: Vec<(&'a str, &'a str)>,
+    /// Everything wrong with the fields seen so far
+    problems: Vec<Problem<'a>>,
 }
 
 #[derive(thiserror::Error, Debug)]
-enum Error {
-    #[error("missing field: {0}")]
-    MissingField(&'static str),
-
+enum Error<'a> {
     #[error("could not parse {0}: {1}")]
-    ParseError(String, String),
+    Parse(String, String),
+
+    #[error("{} problem(s) with passport", .0.len())]
+    Invalid(Vec<Problem<'a>>),
+}
+
+/// What's wrong with a field.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum ProblemKind {
+    /// A required field that never showed up
+    Missing,
+    /// A key that isn't part of a passport
+    UnknownKey,
+    /// The same key twice in one passport
+    Duplicate,
+    /// Not `key:value` at all
+    Malformed,
+    /// Not a number where one was expected
+    NotANumber,
+    /// A number outside the field's bounds
+    OutOfRange,
+    /// A height whose unit isn't one the schema accepts
+    BadUnit,
+    MalformedColor,
+    MalformedId,
+    /// Not one of an enum's values
+    NotAllowed,
+}
+
+/// One problem with one passport, pointing at the text responsible.
+#[derive(Clone, PartialEq, Debug)]
+struct Problem<'a> {
+    kind: ProblemKind,
+    /// The key as written, or the one that was expected for missing fields
+    key: &'a str,
+    /// The value as written, empty for missing fields
+    raw: &'a str,
+    /// Where the whole `key:value` sits in the passport's input, `None` for missing fields
+    span: Option<Range<usize>>,
+}
+
+impl std::fmt::Display for Problem<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            ProblemKind::Missing => "missing",
+            ProblemKind::UnknownKey => "unknown key",
+            ProblemKind::Duplicate => "duplicate key",
+            ProblemKind::Malformed => "not a key:value pair",
+            ProblemKind::NotANumber => "not a number",
+            ProblemKind::OutOfRange => "out of range",
+            ProblemKind::BadUnit => "bad unit",
+            ProblemKind::MalformedColor => "malformed colour",
+            ProblemKind::MalformedId => "malformed id",
+            ProblemKind::NotAllowed => "not an allowed value",
+        };
+        match &self.span {
+            Some(span) => write!(
+                f,
+                "{}:{} (bytes {}..{}): {}",
+                self.key, self.raw, span.start, span.end, reason
+            ),
+            None => write!(f, "{}: {}", self.key, reason),
+        }
+    }
+}
+
+/// `digits` times ten to the `power`, rounded once: dividing by a power of ten rather than
+/// multiplying by a fraction of one keeps `1502mm` at `150.2cm`.
+fn scaled(digits: u64, power: i32) -> f64 {
+    match power {
+        0.. => digits as f64 * 10f64.powi(power),
+        _ => digits as f64 / 10f64.powi(-power),
+    }
+}
+
+peg::parser! {
+    grammar fields() for str {
+        /// Every whitespace-separated token, with its byte offset
+        pub(crate) rule tokens() -> Vec<(usize, &'input str)>
+            = separator()* tokens:(token() ** (separator()+)) separator()* { tokens }
+
+        /// A unit written apart from its number (`hgt:180 cm`) belongs to the same token
+        rule token() -> (usize, &'input str)
+            = start:position!() s:$((!separator() [_])+ (" " unit() &(separator() / ![_]))?) {
+                (start, s)
+            }
+
+        rule separator()
+            = ['\n' | '\r' | ' ']
+
+        pub(crate) rule num() -> u64
+            = s:$(['0'..='9']+) {? s.parse().or(Err("number")) }
+
+        /// The digits as one integer, and how many of them come after the point
+        rule decimal() -> (u64, i32)
+            = whole:$(['0'..='9']+) fraction:("." f:$(['0'..='9']+) { f })? {?
+                let fraction = fraction.unwrap_or("");
+                let digits = format!("{}{}", whole, fraction).parse().or(Err("decimal"))?;
+                Ok((digits, fraction.len() as i32))
+            }
+
+        /// Metric lengths end up in centimeters and imperial ones in inches
+        pub(crate) rule length() -> Length
+            = feet:decimal() "'" inches:(inches:decimal() "\"" { inches })? {?
+                let inches = inches.map_or(0.0, |(digits, places)| scaled(digits, -places));
+                let (digits, places) = feet;
+                let feet = scaled(digits.checked_mul(12).ok_or("length")?, -places);
+                Ok(Length::new(feet + inches, Unit::In))
+            }
+            / value:decimal() " "? unit:unit() {?
+                let ((digits, places), (times, power, unit)) = (value, unit);
+                let digits = digits.checked_mul(times).ok_or("length")?;
+                Ok(Length::new(scaled(digits, power - places), unit))
+            }
+
+        /// How many of which `Unit` one of these is, as a whole number times a power of ten
+        rule unit() -> (u64, i32, Unit)
+            = "cm" { (1, 0, Unit::Cm) }
+            / "mm" { (1, -1, Unit::Cm) }
+            / "m" { (1, 2, Unit::Cm) }
+            / "in" { (1, 0, Unit::In) }
+            / "\"" { (1, 0, Unit::In) }
+            / "ft" { (12, 0, Unit::In) }
+
+        pub(crate) rule hex_color()
+            = "#" ['0'..='9' | 'a'..='f']*<6,6>
+    }
+}
+
+/// Store a field's value, unless the passport already had one.
+fn fill<T>(slot: &mut Option<T>, value: T) -> Result<(), ProblemKind> {
+    match slot {
+        Some(_) => Err(ProblemKind::Duplicate),
+        None => {
+            *slot = Some(value);
+            Ok(())
+        }
+    }
 }
 
 impl<'a> PassportBuilder<'a> {
-    fn build(self) -> Result<Passport<'a>, Error> {
-        // // Verbose first pass...
-        // Ok(Passport {
-        //     birth_year: self.birth_year.ok_or(Error::MissingField("birth_year"))?,
-        //     issue_year: self.issue_year.ok_or(Error::MissingField("issue year"))?,
-        //     expiration_year: self
-        //         .expiration_year
-        //         .ok_or(Error::MissingField("expiration_year"))?,
-        //     height: self.height.ok_or(Error::MissingField("height"))?,
-        //     hair_color: self.hair_color.ok_or(Error::MissingField("hair color"))?,
-        //     eye_color: self.eye_color.ok_or(Error::MissingField("eye_color"))?,
-        //     passport_id: self.passport_id.ok_or(Error::MissingField("passport id"))?,
-        //     country_id: self.country_id,
-        // })
-
-        // // Macro second try (this syntax is _so_ overwhelming)
-        macro_rules! build {
-            (
-                required => {
-                    $($req: ident),* $(,)*
-                }$(,)*
-                optional => {
-                    $($opt: ident),* $(,)*
-                }$(,)*
-            ) => {
-                Ok(Passport {
-                    $($req: self.$req.ok_or(Error::MissingField(stringify!($req)))?),*,
-                    $($opt: self.$opt),*
+    /// Build the passport, or report every problem found while parsing, which includes any
+    /// field the schema requires that never showed up.
+    fn build(self) -> Result<Passport<'a>, Error<'a>> {
+        if !self.problems.is_empty() {
+            return Err(Error::Invalid(self.problems));
+        }
+        Ok(Passport {
+            birth_year: self.birth_year,
+            issue_year: self.issue_year,
+            expiration_year: self.expiration_year,
+            height: self.height,
+            hair_color: self.hair_color,
+            eye_color: self.eye_color,
+            passport_id: self.passport_id,
+            country_id: self.country_id,
+            extra: self.extra,
+        })
+    }
+
+    /// Collect every field in `input` under the builtin schema.
+    fn parse(input: &'a str) -> Result<Self, Error<'a>> {
+        Self::parse_with(input, Schema::builtin())
+    }
+
+    /// Collect every field in `input`. Invalid fields are left out and noted in `problems`
+    /// rather than failing the whole passport, as are required fields that never show up.
+    fn parse_with(input: &'a str, schema: &'a Schema) -> Result<Self, Error<'a>> {
+        let mut b: Self = Default::default();
+        let tokens =
+            fields::tokens(input).map_err(|e| Error::Parse(input.into(), e.to_string()))?;
+        for &(start, token) in &tokens {
+            b.add_field(schema, start..start + token.len(), token);
+        }
+
+        let seen: HashSet<&str> = tokens
+            .iter()
+            .filter_map(|(_, token)| token.split_once(':').map(|(key, _)| key))
+            .collect();
+        for rule in schema.fields.iter().filter(|rule| rule.required) {
+            if !seen.contains(rule.key.as_str()) {
+                b.problems.push(Problem {
+                    kind: ProblemKind::Missing,
+                    key: &rule.key,
+                    raw: "",
+                    span: None,
+                });
+            }
+        }
+        Ok(b)
+    }
+
+    fn add_field(&mut self, schema: &Schema, span: Range<usize>, token: &'a str) {
+        let (key, raw) = match token.split_once(':') {
+            Some(field) => field,
+            None => {
+                return self.problems.push(Problem {
+                    kind: ProblemKind::Malformed,
+                    key: "",
+                    raw: token,
+                    span: Some(span),
                 })
             }
+        };
+
+        let result = match schema.rule(key) {
+            Some(rule) => rule
+                .kind
+                .check(raw)
+                .and_then(|value| self.set_field(key, raw, value)),
+            None => Err(ProblemKind::UnknownKey),
+        };
+        if let Err(kind) = result {
+            self.problems.push(Problem {
+                kind,
+                key,
+                raw,
+                span: Some(span),
+            });
         }
+    }
 
-        build! {
-            required => {
-                birth_year,
-                issue_year,
-                expiration_year,
-                height,
-                hair_color,
-                eye_color,
-                passport_id,
-            },
-            optional => {
-                country_id
+    /// `Schema::check` makes sure every passport key gets the right kind of value.
+    fn set_field(
+        &mut self,
+        key: &'a str,
+        raw: &'a str,
+        value: Value<'a>,
+    ) -> Result<(), ProblemKind> {
+        match (key, value) {
+            ("byr", Value::Year(v)) => fill(&mut self.birth_year, v),
+            ("iyr", Value::Year(v)) => fill(&mut self.issue_year, v),
+            ("eyr", Value::Year(v)) => fill(&mut self.expiration_year, v),
+            ("hgt", Value::Length(v)) => fill(&mut self.height, v),
+            ("hcl", Value::Color(v)) => fill(&mut self.hair_color, v),
+            ("ecl", Value::Color(v)) => fill(&mut self.eye_color, v),
+            ("pid", Value::Id(v)) => fill(&mut self.passport_id, v),
+            ("cid", Value::Id(v)) => fill(&mut self.country_id, v),
+            _ if self.extra.iter().any(|&(k, _)| k == key) => Err(ProblemKind::Duplicate),
+            _ => {
+                self.extra.push((key, raw));
+                Ok(())
             }
         }
     }
+}
 
-    fn parse(input: &'a str) -> Result<Self, Error> {
-        let mut b: Self = Default::default();
+/// A field's value, once its rule has accepted it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Value<'a> {
+    Year(Year),
+    Length(Length),
+    Color(Color<'a>),
+    Id(ID<'a>),
+}
+
+mod schema {
+    use super::{fields, Color, ProblemKind, Unit, Value, Year, ID};
+    use serde::{Deserialize, Serialize};
+    use std::{path::Path, sync::OnceLock};
+
+    /// Which fields a passport has and what they may hold.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub(crate) struct Schema {
+        #[serde(rename = "field")]
+        pub(crate) fields: Vec<FieldRule>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub(crate) struct FieldRule {
+        pub(crate) key: String,
+        #[serde(default = "required_by_default")]
+        pub(crate) required: bool,
+        #[serde(flatten)]
+        pub(crate) kind: FieldType,
+    }
+
+    fn required_by_default() -> bool {
+        true
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub(crate) enum FieldType {
+        Year(Bounds),
+        /// Any length, converted to `unit` and checked against `min..=max`, give or take
+        /// `tolerance` (in `unit`) for rounding in other units
+        Length {
+            unit: Unit,
+            min: f64,
+            max: f64,
+            #[serde(default)]
+            tolerance: f64,
+        },
+        /// `#` and six lowercase hex digits
+        HexColor,
+        Enum {
+            values: Vec<String>,
+        },
+        /// Exactly `width` decimal digits
+        Digits {
+            width: usize,
+        },
+        /// Anything at all
+        Text,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub(crate) struct Bounds {
+        pub(crate) min: u64,
+        pub(crate) max: u64,
+    }
+
+    impl Bounds {
+        fn check(&self, num: u64) -> Result<u64, ProblemKind> {
+            if (self.min..=self.max).contains(&num) {
+                Ok(num)
+            } else {
+                Err(ProblemKind::OutOfRange)
+            }
+        }
+    }
 
-        peg::parser! {
-            grammar parser() for str {
-                pub(crate) rule root(b: &mut PassportBuilder<'input>)
-                    = (field(b) separator()*)* ![_]
-
-                rule separator()
-                    = ['\n' | '\r' | ' ']
-
-                rule field(b: &mut PassportBuilder<'input>)
-                    // years
-                    = byr(b) / iyr(b) / eyr(b)
-                    // height
-                    / hgt(b)
-                    // colors
-                    / hcl(b) / ecl(b)
-                    // IDs
-                    / pid(b) / cid(b)
-
-                rule byr(b: &mut PassportBuilder<'input>)
-                    = "byr:" year:year((1920..=2002)) { b.birth_year = Some(year) }
-
-                rule iyr(b: &mut PassportBuilder<'input>)
-                    = "iyr:" year:year((2010..=2020)) { b.issue_year = Some(year) }
-
-                rule eyr(b: &mut PassportBuilder<'input>)
-                    = "eyr:" year:year((2020..=2030)) { b.expiration_year = Some(year) }
-
-                rule hgt(b: &mut PassportBuilder<'input>)
-                    = "hgt:" height:length() {?
-                        match &height {
-                            Length::Cm(v) if !(150..=193).contains(v) => {
-                                Err("bad height (cm)")
-                            },
-                            Length::In(v) if !(59..=76).contains(v) => {
-                                Err("bad height (in")
-                            },
-                            _ => {
-                                b.height = Some(height);
-                                Ok(())
-                            }
+    impl FieldType {
+        pub(crate) fn check<'a>(&self, raw: &'a str) -> Result<Value<'a>, ProblemKind> {
+            match self {
+                FieldType::Year(bounds) => {
+                    let num = fields::num(raw).map_err(|_| ProblemKind::NotANumber)?;
+                    Ok(Value::Year(Year(bounds.check(num)?)))
+                }
+                FieldType::Length {
+                    unit,
+                    min,
+                    max,
+                    tolerance,
+                } => {
+                    let length = fields::length(raw).map_err(|_| {
+                        // a number with a unit we don't know, or no number at all?
+                        match raw.starts_with(|c: char| c.is_ascii_digit()) {
+                            true => ProblemKind::BadUnit,
+                            false => ProblemKind::NotANumber,
                         }
+                    })?;
+                    let canonical = length.to(*unit).value;
+                    if (min - tolerance..=max + tolerance).contains(&canonical) {
+                        Ok(Value::Length(length))
+                    } else {
+                        Err(ProblemKind::OutOfRange)
                     }
+                }
+                FieldType::HexColor => fields::hex_color(raw)
+                    .map(|_| Value::Color(Color(raw)))
+                    .map_err(|_| ProblemKind::MalformedColor),
+                FieldType::Enum { values } => match values.iter().any(|v| v == raw) {
+                    true => Ok(Value::Color(Color(raw))),
+                    false => Err(ProblemKind::NotAllowed),
+                },
+                FieldType::Digits { width } => {
+                    match raw.len() == *width && raw.bytes().all(|b| b.is_ascii_digit()) {
+                        true => Ok(Value::Id(ID(raw))),
+                        false => Err(ProblemKind::MalformedId),
+                    }
+                }
+                FieldType::Text => Ok(Value::Id(ID(raw))),
+            }
+        }
+    }
 
-                rule pid(b: &mut PassportBuilder<'input>)
-                    = "pid:" id:$(['0'..='9']*<9,9>) { b.passport_id = Some(ID(id)) }
+    #[derive(thiserror::Error, Debug)]
+    pub(crate) enum SchemaError {
+        #[error("could not read schema: {0}")]
+        Io(#[from] std::io::Error),
 
-                rule cid(b: &mut PassportBuilder<'input>)
-                    = "cid:" id:$((!separator()[_])+) { b.country_id = Some(ID(id)) }
+        #[error("invalid TOML schema: {0}")]
+        Toml(#[from] toml::de::Error),
 
-                rule hcl(b: &mut PassportBuilder<'input>)
-                    = "hcl:" color:hcl0() { b.hair_color = Some(color) }
+        #[error("invalid JSON schema: {0}")]
+        Json(#[from] serde_json::Error),
 
-                rule hcl0() -> Color<'input>
-                    = s:$("#" ['0'..='9' | 'a'..='f']*<6,6>) { Color(s) }
+        #[error("unusable schema: {0}")]
+        Unusable(String),
+    }
 
-                rule ecl(b: &mut PassportBuilder<'input>)
-                    = "ecl:" color:ecl0() { b.eye_color = Some(color) }
+    impl Schema {
+        /// The puzzle's own rules, from `schema.toml`.
+        pub(crate) fn builtin() -> &'static Schema {
+            static BUILTIN: OnceLock<Schema> = OnceLock::new();
+            BUILTIN.get_or_init(|| {
+                Schema::from_toml(include_str!("schema.toml")).expect("builtin schema is valid")
+            })
+        }
 
-                rule ecl0() -> Color<'input>
-                    = s:$("amb" / "blu" / "brn" / "gry" / "grn" / "hzl" / "oth") { Color(s) }
+        pub(crate) fn from_toml(s: &str) -> Result<Self, SchemaError> {
+            toml::from_str::<Self>(s)?.check()
+        }
 
-                rule year(range: RangeInclusive<u64>) -> Year
-                    = num:num() {?
-                    if range.contains(&num) {
-                        Ok(Year(num))
-                    } else {
-                        Err("year out of range")
+        pub(crate) fn from_json(s: &str) -> Result<Self, SchemaError> {
+            serde_json::from_str::<Self>(s)?.check()
+        }
+
+        /// Read a schema from disk: JSON if the file ends in `.json`, TOML otherwise.
+        pub(crate) fn load(path: &Path) -> Result<Self, SchemaError> {
+            let s = std::fs::read_to_string(path)?;
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("json") => Self::from_json(&s),
+                _ => Self::from_toml(&s),
+            }
+        }
+
+        pub(crate) fn rule(&self, key: &str) -> Option<&FieldRule> {
+            self.fields.iter().find(|rule| rule.key == key)
+        }
+
+        /// Passports have a typed place for each of the puzzle's keys, so a schema has to give
+        /// those a type that fits. Anything else goes, including leaving them out.
+        fn check(self) -> Result<Self, SchemaError> {
+            let unusable = |msg: String| Err(SchemaError::Unusable(msg));
+
+            for (i, rule) in self.fields.iter().enumerate() {
+                if self.fields[..i].iter().any(|other| other.key == rule.key) {
+                    return unusable(format!("{} is declared twice", rule.key));
+                }
+                let fits = match (rule.key.as_str(), &rule.kind) {
+                    ("byr" | "iyr" | "eyr", FieldType::Year(_)) => true,
+                    ("hgt", FieldType::Length { .. }) => true,
+                    ("hcl" | "ecl", FieldType::HexColor | FieldType::Enum { .. }) => true,
+                    ("pid" | "cid", FieldType::Digits { .. } | FieldType::Text) => true,
+                    ("byr" | "iyr" | "eyr" | "hgt" | "hcl" | "ecl" | "pid" | "cid", _) => false,
+                    _ => true,
+                };
+                if !fits {
+                    return unusable(format!("{} can't be a {:?}", rule.key, rule.kind));
+                }
+            }
+            Ok(self)
+        }
+    }
+}
+
+mod batch {
+    use super::{Color, Error, Length, Passport, PassportBuilder, ProblemKind, Schema, Year, ID};
+    use serde::{Deserialize, Serialize};
+    use std::{
+        collections::BTreeMap,
+        io::{BufRead, Read, Write},
+    };
+
+    /// A [`Passport`] that owns its data, so it can outlive its input and be written out.
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    pub(crate) struct OwnedPassport {
+        /// Position in the batch it was read from, from 0, as in [`Record::index`]
+        #[serde(skip)]
+        pub(crate) record: usize,
+        pub(crate) birth_year: Option<Year>,
+        pub(crate) issue_year: Option<Year>,
+        pub(crate) expiration_year: Option<Year>,
+        pub(crate) height: Option<Length>,
+        pub(crate) hair_color: Option<String>,
+        pub(crate) eye_color: Option<String>,
+        pub(crate) passport_id: Option<String>,
+        pub(crate) country_id: Option<String>,
+        #[serde(default)]
+        pub(crate) extra: BTreeMap<String, String>,
+    }
+
+    impl OwnedPassport {
+        /// `p`, which was `record` in its batch.
+        pub(crate) fn new(record: usize, p: &Passport<'_>) -> Self {
+            Self {
+                record,
+                birth_year: p.birth_year,
+                issue_year: p.issue_year,
+                expiration_year: p.expiration_year,
+                height: p.height,
+                hair_color: p.hair_color.map(|c| c.0.into()),
+                eye_color: p.eye_color.map(|c| c.0.into()),
+                passport_id: p.passport_id.map(|id| id.0.into()),
+                country_id: p.country_id.map(|id| id.0.into()),
+                extra: p.extra.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
+            }
+        }
+
+        /// Borrow this as an ordinary passport.
+        pub(crate) fn as_passport(&self) -> Passport<'_> {
+            Passport {
+                birth_year: self.birth_year,
+                issue_year: self.issue_year,
+                expiration_year: self.expiration_year,
+                height: self.height,
+                hair_color: self.hair_color.as_deref().map(Color),
+                eye_color: self.eye_color.as_deref().map(Color),
+                passport_id: self.passport_id.as_deref().map(ID),
+                country_id: self.country_id.as_deref().map(ID),
+                extra: self
+                    .extra
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .collect(),
+            }
+        }
+
+        /// The puzzle's fields as `(key, value)`, in the puzzle's order, `None` where missing.
+        fn known_fields(&self) -> [(&'static str, Option<String>); 8] {
+            let year = |year: Option<Year>| year.map(|year| year.0.to_string());
+            [
+                ("byr", year(self.birth_year)),
+                ("iyr", year(self.issue_year)),
+                ("eyr", year(self.expiration_year)),
+                ("hgt", self.height.map(|height| height.to_string())),
+                ("hcl", self.hair_color.clone()),
+                ("ecl", self.eye_color.clone()),
+                ("pid", self.passport_id.clone()),
+                ("cid", self.country_id.clone()),
+            ]
+        }
+
+        /// Every field there is as `(key, value)`, in the puzzle's order, then extras by key.
+        pub(crate) fn fields(&self) -> Vec<(&str, String)> {
+            let mut fields: Vec<(&str, String)> = self
+                .known_fields()
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value?)))
+                .collect();
+            fields.extend(self.extra.iter().map(|(k, v)| (k.as_str(), v.clone())));
+            fields
+        }
+    }
+
+    const CSV_HEADER: [&str; 9] = [
+        "birth_year",
+        "issue_year",
+        "expiration_year",
+        "height",
+        "hair_color",
+        "eye_color",
+        "passport_id",
+        "country_id",
+        "extra",
+    ];
+
+    #[derive(thiserror::Error, Debug)]
+    pub(crate) enum ImportError {
+        #[error("record {0}: {1}")]
+        Json(usize, serde_json::Error),
+
+        #[error("{0}")]
+        Csv(#[from] csv::Error),
+
+        #[error("record {record}: {}", .problems.join(", "))]
+        Invalid {
+            record: usize,
+            problems: Vec<String>,
+        },
+    }
+
+    /// One JSON object per line.
+    pub(crate) fn write_json_lines(
+        passports: &[OwnedPassport],
+        mut out: impl Write,
+    ) -> std::io::Result<()> {
+        for passport in passports {
+            serde_json::to_writer(&mut out, passport)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_json_lines(
+        s: &str,
+        schema: &Schema,
+    ) -> Result<Vec<OwnedPassport>, ImportError> {
+        s.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| {
+                let passport: OwnedPassport =
+                    serde_json::from_str(line).map_err(|e| ImportError::Json(i, e))?;
+                // back to the puzzle's notation, so it gets the same checks as anything else
+                let fields: Vec<String> = passport
+                    .fields()
+                    .into_iter()
+                    .map(|(key, value)| format!("{}:{}", key, value))
+                    .collect();
+                import(i, &fields.join(" "), schema)
+            })
+            .collect()
+    }
+
+    /// A header row, then one row per passport. Extra fields share the last column, as
+    /// space-separated `key:value` pairs.
+    pub(crate) fn write_csv(passports: &[OwnedPassport], out: impl Write) -> csv::Result<()> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(CSV_HEADER)?;
+        for passport in passports {
+            let extra: Vec<String> = passport
+                .extra
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect();
+            let mut row: Vec<String> = passport
+                .known_fields()
+                .into_iter()
+                .map(|(_, value)| value.unwrap_or_default())
+                .collect();
+            row.push(extra.join(" "));
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read rows written by [`write_csv`], validating each one against `schema`.
+    pub(crate) fn read_csv(s: &str, schema: &Schema) -> Result<Vec<OwnedPassport>, ImportError> {
+        let keys = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+        let mut reader = csv::Reader::from_reader(s.as_bytes());
+        let mut passports = vec![];
+        for (i, row) in reader.records().enumerate() {
+            let row = row?;
+            // back to the puzzle's notation, so it gets the same checks as anything else
+            let mut fields: Vec<String> = keys
+                .iter()
+                .zip(row.iter())
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(key, value)| format!("{}:{}", key, value))
+                .collect();
+            fields.extend(row.get(keys.len()).map(String::from));
+            passports.push(import(i, &fields.join(" "), schema)?);
+        }
+        Ok(passports)
+    }
+
+    /// The puzzle's own format: one passport per line, each followed by a blank line.
+    pub(crate) fn write_batch(
+        passports: &[OwnedPassport],
+        mut out: impl Write,
+    ) -> std::io::Result<()> {
+        for (i, passport) in passports.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            let fields: Vec<String> = passport
+                .fields()
+                .into_iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect();
+            writeln!(out, "{}", fields.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Read blank-line-separated passports, insisting every one of them is valid.
+    pub(crate) fn read_batch(s: &str, schema: &Schema) -> Result<Vec<OwnedPassport>, ImportError> {
+        s.split("\n\n")
+            .filter(|record| !record.trim().is_empty())
+            .enumerate()
+            .map(|(i, record)| import(i, record, schema))
+            .collect()
+    }
+
+    /// One passport's worth of a batch, read by [`Records`].
+    #[derive(Clone, PartialEq, Debug)]
+    pub(crate) struct Record {
+        /// Position in the batch, from 0
+        pub(crate) index: usize,
+        /// Line number the record starts on, from 1
+        pub(crate) line: usize,
+        /// The record's lines with trailing whitespace (and `\r`) removed, joined by `\n`
+        pub(crate) text: String,
+    }
+
+    /// Reads a batch one record at a time, so only the current record is ever in memory.
+    pub(crate) struct Records<R> {
+        reader: R,
+        line: Vec<u8>,
+        line_number: usize,
+        index: usize,
+        max_record_len: usize,
+    }
+
+    impl<R: BufRead> Records<R> {
+        pub(crate) fn new(reader: R) -> Self {
+            Self {
+                reader,
+                line: vec![],
+                line_number: 0,
+                index: 0,
+                max_record_len: 64 * 1024,
+            }
+        }
+
+        /// Give up on records longer than this many bytes instead of buffering them.
+        pub(crate) fn max_record_len(self, max_record_len: usize) -> Self {
+            Self {
+                max_record_len,
+                ..self
+            }
+        }
+
+        /// Read the next line into `line`, or as much of it as a record could hold and skip
+        /// the rest. `None` at the end of the input, otherwise whether the line fit.
+        fn read_line(&mut self) -> std::io::Result<Option<bool>> {
+            self.line.clear();
+            // the longest line a record can have, `\r\n` included
+            let limit = self.max_record_len as u64 + 2;
+            let read = Read::take(&mut self.reader, limit).read_until(b'\n', &mut self.line)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+
+            let fits = self.line.ends_with(b"\n") || (read as u64) < limit;
+            if !fits {
+                loop {
+                    let buf = self.reader.fill_buf()?;
+                    let (used, done) = match buf.iter().position(|&b| b == b'\n') {
+                        Some(i) => (i + 1, true),
+                        None => (buf.len(), buf.is_empty()),
+                    };
+                    self.reader.consume(used);
+                    if done {
+                        break;
                     }
                 }
+            }
+            Ok(Some(fits))
+        }
+
+        /// Give up on record `self.index`, which starts on `line`, because it's too long.
+        fn too_long(&mut self, line: usize) -> std::io::Error {
+            let reason = format!("is over {} bytes", self.max_record_len);
+            self.give_up(line, reason)
+        }
+
+        /// Give up on record `self.index`, which starts on `line`: skip to the blank line after
+        /// it so the next call picks up with the record after.
+        fn give_up(&mut self, line: usize, reason: impl std::fmt::Display) -> std::io::Error {
+            let error = std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("record {} (line {}) {}", self.index, line, reason),
+            );
+            self.index += 1;
+            loop {
+                match self.read_line() {
+                    Ok(Some(true)) if self.line.trim_ascii().is_empty() => return error,
+                    Ok(Some(_)) => {}
+                    Ok(None) => return error,
+                    Err(e) => return e,
+                }
+            }
+        }
+    }
 
-                rule length() -> Length
-                    = num:num() "cm" { Length::Cm(num) }
-                    / num:num() "in" { Length::In(num) }
+    impl<R: BufRead> Iterator for Records<R> {
+        type Item = std::io::Result<Record>;
 
-                rule num() -> u64
-                    = s:$(['0'..='9']+) { s.parse().unwrap() }
+        fn next(&mut self) -> Option<Self::Item> {
+            let mut record: Option<Record> = None;
+            loop {
+                match self.read_line() {
+                    Ok(Some(true)) => {}
+                    Ok(Some(false)) => {
+                        let start = record.map_or(self.line_number, |r| r.line);
+                        return Some(Err(self.too_long(start)));
+                    }
+                    Ok(None) => break,
+                    Err(e) => return Some(Err(e)),
+                }
+
+                let line = match std::str::from_utf8(&self.line) {
+                    Ok(line) => line.trim_end(),
+                    Err(e) => {
+                        let start = record.map_or(self.line_number, |r| r.line);
+                        return Some(Err(self.give_up(start, format_args!("isn't UTF-8: {}", e))));
+                    }
+                };
+                match (&mut record, line.is_empty()) {
+                    // any number of blank lines between records
+                    (None, true) => continue,
+                    (Some(_), true) => break,
+                    (None, false) => {
+                        record = Some(Record {
+                            index: self.index,
+                            line: self.line_number,
+                            text: line.to_owned(),
+                        })
+                    }
+                    (Some(record), false) => {
+                        record.text.push('\n');
+                        record.text.push_str(line);
+                    }
+                }
 
-                rule color() -> Color<'input>
-                    = s:$((!separator()[_])*) { Color(s) }
+                if let Some(r) = record
+                    .as_ref()
+                    .filter(|r| r.text.len() > self.max_record_len)
+                {
+                    let start = r.line;
+                    return Some(Err(self.too_long(start)));
+                }
             }
+
+            self.index += record.is_some() as usize;
+            record.map(Ok)
         }
+    }
 
-        parser::root(input, &mut b).map_err(|e| Error::ParseError(input.into(), e.to_string()))?;
-        Ok(b)
+    /// Running totals over a batch, fed one record at a time.
+    #[derive(Clone, PartialEq, Debug, Default)]
+    pub(crate) struct Summary {
+        pub(crate) records: usize,
+        pub(crate) valid: usize,
+        /// How often each key had each kind of problem
+        pub(crate) problems: BTreeMap<(String, ProblemKind), usize>,
+    }
+
+    impl Summary {
+        /// Validate `record` and count the outcome, handing back the passport if it's valid.
+        pub(crate) fn add(&mut self, record: &Record, schema: &Schema) -> Option<OwnedPassport> {
+            self.records += 1;
+            match PassportBuilder::parse_with(&record.text, schema).and_then(|b| b.build()) {
+                Ok(passport) => {
+                    self.valid += 1;
+                    Some(OwnedPassport::new(record.index, &passport))
+                }
+                Err(Error::Invalid(problems)) => {
+                    for problem in problems {
+                        *self
+                            .problems
+                            .entry((problem.key.to_owned(), problem.kind))
+                            .or_default() += 1;
+                    }
+                    None
+                }
+                Err(_) => None,
+            }
+        }
+    }
+
+    impl std::fmt::Display for Summary {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            writeln!(f, "{} of {} passports valid", self.valid, self.records)?;
+            for ((key, kind), count) in &self.problems {
+                writeln!(f, "{:>8} {} {:?}", count, key, kind)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn import(record: usize, input: &str, schema: &Schema) -> Result<OwnedPassport, ImportError> {
+        match PassportBuilder::parse_with(input, schema).and_then(|b| b.build()) {
+            Ok(passport) => Ok(OwnedPassport::new(record, &passport)),
+            Err(Error::Invalid(problems)) => Err(ImportError::Invalid {
+                record,
+                problems: problems.iter().map(ToString::to_string).collect(),
+            }),
+            Err(e) => Err(ImportError::Invalid {
+                record,
+                problems: vec![e.to_string()],
+            }),
+        }
+    }
+}
+
+/// Checks that need more than one field, or more than one passport, once every field is
+/// known to be fine on its own.
+mod consistency {
+    use super::{OwnedPassport, Year};
+    use std::collections::BTreeMap;
+
+    /// What counts as plausible.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct Limits {
+        /// Youngest and oldest anyone can be when a passport's issued
+        pub(crate) age_at_issue: (u64, u64),
+        /// Longest a passport can be valid for, in years
+        pub(crate) validity: u64,
+    }
+
+    impl Default for Limits {
+        fn default() -> Self {
+            Self {
+                age_at_issue: (0, 110),
+                validity: 10,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) enum FindingKind {
+        /// Issued after the holder was born? Not this one.
+        IssuedBeforeBirth {
+            born: Year,
+            issued: Year,
+        },
+        ImplausibleAge {
+            age: u64,
+        },
+        ExpiresBeforeIssue {
+            issued: Year,
+            expires: Year,
+        },
+        TooLongValid {
+            years: u64,
+        },
+        /// Several passports share a passport id
+        DuplicatePassportId {
+            passport_id: String,
+        },
+        /// Passports with the same `cid` belong to the same person, but disagree on these
+        ConflictingCountryId {
+            country_id: String,
+            fields: Vec<&'static str>,
+        },
+    }
+
+    /// Something impossible about one or more passports, by the record they were read from.
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) struct Finding {
+        pub(crate) records: Vec<usize>,
+        pub(crate) kind: FindingKind,
+    }
+
+    impl std::fmt::Display for Finding {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let records: Vec<String> = self.records.iter().map(ToString::to_string).collect();
+            write!(f, "passport {}: ", records.join(", "))?;
+            match &self.kind {
+                FindingKind::IssuedBeforeBirth { born, issued } => {
+                    write!(f, "issued in {} before birth in {}", issued.0, born.0)
+                }
+                FindingKind::ImplausibleAge { age } => write!(f, "issued at age {}", age),
+                FindingKind::ExpiresBeforeIssue { issued, expires } => {
+                    write!(f, "expires in {} before issue in {}", expires.0, issued.0)
+                }
+                FindingKind::TooLongValid { years } => write!(f, "valid for {} years", years),
+                FindingKind::DuplicatePassportId { passport_id } => {
+                    write!(f, "share passport id {}", passport_id)
+                }
+                FindingKind::ConflictingCountryId { country_id, fields } => write!(
+                    f,
+                    "share country id {} but not {}",
+                    country_id,
+                    fields.join(", ")
+                ),
+            }
+        }
+    }
+
+    /// Everything wrong with one passport.
+    pub(crate) fn check_passport(p: &OwnedPassport, limits: &Limits) -> Vec<Finding> {
+        let mut kinds = vec![];
+
+        // only what the passport has can disagree
+        if let (Some(born), Some(issued)) = (p.birth_year, p.issue_year) {
+            match issued.0.checked_sub(born.0) {
+                None => kinds.push(FindingKind::IssuedBeforeBirth { born, issued }),
+                Some(age) if age < limits.age_at_issue.0 || age > limits.age_at_issue.1 => {
+                    kinds.push(FindingKind::ImplausibleAge { age })
+                }
+                Some(_) => {}
+            }
+        }
+        if let (Some(issued), Some(expires)) = (p.issue_year, p.expiration_year) {
+            match expires.0.checked_sub(issued.0) {
+                // a passport can run out the year it's issued, just not before
+                None => kinds.push(FindingKind::ExpiresBeforeIssue { issued, expires }),
+                Some(years) if years > limits.validity => {
+                    kinds.push(FindingKind::TooLongValid { years })
+                }
+                Some(_) => {}
+            }
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| Finding {
+                records: vec![p.record],
+                kind,
+            })
+            .collect()
+    }
+
+    /// Everything wrong with each passport, then everything wrong between them.
+    pub(crate) fn check_batch(passports: &[OwnedPassport], limits: &Limits) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = passports
+            .iter()
+            .flat_map(|p| check_passport(p, limits))
+            .collect();
+        let records =
+            |positions: &[usize]| positions.iter().map(|&i| passports[i].record).collect();
+
+        let mut by_passport_id: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        let mut by_country_id: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, p) in passports.iter().enumerate() {
+            if let Some(pid) = &p.passport_id {
+                by_passport_id.entry(pid).or_default().push(i);
+            }
+            if let Some(cid) = &p.country_id {
+                by_country_id.entry(cid).or_default().push(i);
+            }
+        }
+
+        for (passport_id, positions) in by_passport_id {
+            if positions.len() > 1 {
+                findings.push(Finding {
+                    records: records(&positions),
+                    kind: FindingKind::DuplicatePassportId {
+                        passport_id: passport_id.into(),
+                    },
+                });
+            }
+        }
+
+        for (country_id, positions) in by_country_id {
+            let first = &passports[positions[0]];
+            let differs = |same: &dyn Fn(&OwnedPassport) -> bool| {
+                !positions[1..].iter().all(|&i| same(&passports[i]))
+            };
+            let mut fields = vec![];
+            if differs(&|p| p.birth_year == first.birth_year) {
+                fields.push("birth_year");
+            }
+            if differs(&|p| p.eye_color == first.eye_color) {
+                fields.push("eye_color");
+            }
+            if !fields.is_empty() {
+                findings.push(Finding {
+                    records: records(&positions),
+                    kind: FindingKind::ConflictingCountryId {
+                        country_id: country_id.into(),
+                        fields,
+                    },
+                });
+            }
+        }
+
+        findings
+    }
+}
+
+/// Filters over passports, like `ecl=blu and byr<1950 and hgt.unit=in`.
+///
+/// A comparison is a field, one of `= != < <= > >=`, and a value. Fields are the puzzle's keys
+/// (or any extra key), and `hgt` (in centimetres), `hgt.value`, `hgt.unit`, `hgt.cm` and
+/// `hgt.in` for heights. Years and heights compare as numbers and everything else as text.
+/// Comparisons combine with `and`, `or`, `not` and parentheses. A passport without the field
+/// doesn't match, whatever the comparison.
+mod query {
+    use super::{Passport, Unit};
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub(crate) enum Attr {
+        /// The number as written
+        Value,
+        Unit,
+        /// Converted to this unit
+        In(Unit),
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub(crate) struct Field {
+        key: String,
+        attr: Option<Attr>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub(crate) enum Op {
+        Eq,
+        Ne,
+        Lt,
+        Le,
+        Gt,
+        Ge,
+    }
+
+    /// A field's value on one passport.
+    #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+    pub(crate) enum Scalar<'a> {
+        Num(f64),
+        Text(&'a str),
+    }
+
+    impl std::fmt::Display for Scalar<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Scalar::Num(n) => write!(f, "{}", n),
+                Scalar::Text(s) => write!(f, "{}", s),
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub(crate) enum Expr {
+        Compare(Field, Op, String),
+        And(Box<Expr>, Box<Expr>),
+        Or(Box<Expr>, Box<Expr>),
+        Not(Box<Expr>),
+    }
+
+    impl Field {
+        fn new(key: &str, attr: Option<Attr>) -> Result<Self, &'static str> {
+            if attr.is_some() && key != "hgt" {
+                return Err("only hgt has .value, .unit, .cm and .in");
+            }
+            Ok(Self {
+                key: key.to_owned(),
+                attr,
+            })
+        }
+
+        fn is_numeric(&self) -> bool {
+            matches!(
+                (self.key.as_str(), self.attr),
+                ("byr" | "iyr" | "eyr", _) | ("hgt", None | Some(Attr::Value | Attr::In(_)))
+            )
+        }
+
+        pub(crate) fn value<'a>(&self, p: &Passport<'a>) -> Option<Scalar<'a>> {
+            let year = |year: super::Year| Some(Scalar::Num(year.0 as f64));
+            match (self.key.as_str(), self.attr) {
+                ("byr", _) => year(p.birth_year?),
+                ("iyr", _) => year(p.issue_year?),
+                ("eyr", _) => year(p.expiration_year?),
+                ("hgt", None) => Some(Scalar::Num(p.height?.to(Unit::Cm).value)),
+                ("hgt", Some(Attr::Value)) => Some(Scalar::Num(p.height?.value)),
+                ("hgt", Some(Attr::In(unit))) => Some(Scalar::Num(p.height?.to(unit).value)),
+                ("hgt", Some(Attr::Unit)) => Some(Scalar::Text(match p.height?.unit {
+                    Unit::Cm => "cm",
+                    Unit::In => "in",
+                })),
+                ("hcl", _) => p.hair_color.map(|c| Scalar::Text(c.0)),
+                ("ecl", _) => p.eye_color.map(|c| Scalar::Text(c.0)),
+                ("pid", _) => p.passport_id.map(|id| Scalar::Text(id.0)),
+                ("cid", _) => p.country_id.map(|id| Scalar::Text(id.0)),
+                (key, _) => p
+                    .extra
+                    .iter()
+                    .find(|&&(k, _)| k == key)
+                    .map(|&(_, v)| Scalar::Text(v)),
+            }
+        }
+    }
+
+    impl Expr {
+        fn compare(field: Field, op: Op, value: &str) -> Result<Self, &'static str> {
+            if field.is_numeric() && value.parse::<f64>().is_err() {
+                return Err("a number");
+            }
+            Ok(Expr::Compare(field, op, value.to_owned()))
+        }
+
+        pub(crate) fn matches(&self, p: &Passport<'_>) -> bool {
+            match self {
+                Expr::And(a, b) => a.matches(p) && b.matches(p),
+                Expr::Or(a, b) => a.matches(p) || b.matches(p),
+                Expr::Not(e) => !e.matches(p),
+                Expr::Compare(field, op, value) => {
+                    let ordering = match field.value(p) {
+                        // `compare` made sure this parses
+                        Some(Scalar::Num(n)) => n.partial_cmp(&value.parse().unwrap()),
+                        Some(Scalar::Text(s)) => Some(s.cmp(value.as_str())),
+                        None => None,
+                    };
+                    ordering.is_some_and(|ordering| match op {
+                        Op::Eq => ordering.is_eq(),
+                        Op::Ne => ordering.is_ne(),
+                        Op::Lt => ordering.is_lt(),
+                        Op::Le => ordering.is_le(),
+                        Op::Gt => ordering.is_gt(),
+                        Op::Ge => ordering.is_ge(),
+                    })
+                }
+            }
+        }
+    }
+
+    peg::parser! {
+        grammar parser() for str {
+            pub(crate) rule query() -> Expr
+                = _ e:expr() _ { e }
+
+            rule expr() -> Expr = precedence! {
+                a:(@) __ "or" __ b:@ { Expr::Or(Box::new(a), Box::new(b)) }
+                --
+                a:(@) __ "and" __ b:@ { Expr::And(Box::new(a), Box::new(b)) }
+                --
+                "not" __ e:@ { Expr::Not(Box::new(e)) }
+                --
+                "(" _ e:expr() _ ")" { e }
+                c:comparison() { c }
+            }
+
+            rule comparison() -> Expr
+                = field:field() _ op:op() _ value:$((!['(' | ')' | ' ' | '\t'] [_])+) {?
+                    Expr::compare(field, op, value)
+                }
+
+            pub(crate) rule field() -> Field
+                = key:$(['a'..='z' | 'A'..='Z' | '0'..='9' | '_']+) attr:("." a:attr() { a })? {?
+                    Field::new(key, attr)
+                }
+
+            rule attr() -> Attr
+                = "value" { Attr::Value }
+                / "unit" { Attr::Unit }
+                / "cm" { Attr::In(Unit::Cm) }
+                / "in" { Attr::In(Unit::In) }
+
+            rule op() -> Op
+                = "<=" { Op::Le }
+                / ">=" { Op::Ge }
+                / "!=" { Op::Ne }
+                / "=" { Op::Eq }
+                / "<" { Op::Lt }
+                / ">" { Op::Gt }
+
+            rule _ = [' ' | '\t']*
+            rule __ = [' ' | '\t']+
+        }
+    }
+
+    pub(crate) type ParseError = peg::error::ParseError<peg::str::LineCol>;
+
+    pub(crate) fn parse(query: &str) -> Result<Expr, ParseError> {
+        parser::query(query)
+    }
+
+    pub(crate) fn parse_field(field: &str) -> Result<Field, ParseError> {
+        parser::field(field)
+    }
+
+    /// How many passports have each value of `field`, leaving out those without one.
+    pub(crate) fn histogram<'a>(
+        passports: impl IntoIterator<Item = &'a Passport<'a>>,
+        field: &Field,
+    ) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for value in passports.into_iter().filter_map(|p| field.value(p)) {
+            *counts.entry(value.to_string()).or_default() += 1;
+        }
+        counts
     }
 }
 
@@ -202,21 +1376,202 @@ impl<'a> PassportBuilder<'a> {
 //     results.filter(Result::is_ok).count()
 // }
 
+/// Every valid passport in `s`, ready to be written out.
+fn valid_passports(s: &str, schema: &Schema) -> Vec<OwnedPassport> {
+    s.split("\n\n")
+        .filter(|input| !input.trim().is_empty())
+        .enumerate()
+        .filter_map(|(i, input)| {
+            let passport = PassportBuilder::parse_with(input, schema)
+                .ok()?
+                .build()
+                .ok()?;
+            Some(OwnedPassport::new(i, &passport))
+        })
+        .collect()
+}
+
 fn calc_part_two(s: &str) -> usize {
+    count_valid(s, Schema::builtin())
+}
+
+fn count_valid(s: &str, schema: &Schema) -> usize {
     let results = s
         .split("\n\n")
-        .map(|input| PassportBuilder::parse(input).and_then(|b| b.build()));
+        .map(|input| PassportBuilder::parse_with(input, schema).and_then(|b| b.build()));
 
     results.filter(Result::is_ok).count()
 }
 
+/// Every problem with every invalid passport in `s`, by passport number (from 0).
+fn problems<'a>(s: &'a str, schema: &'a Schema) -> Vec<(usize, Vec<Problem<'a>>)> {
+    s.split("\n\n")
+        .enumerate()
+        .filter_map(|(i, input)| {
+            // the tokenizer takes anything, so every failure is `Invalid`
+            match PassportBuilder::parse_with(input, schema).and_then(|b| b.build()) {
+                Err(Error::Invalid(problems)) => Some((i, problems)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 fn main() {
     // dbg!(FULL);
     // let num_valid = calc_part_one(FULL);
     // println!("Found {}", num_valid);
 
+    let args: Vec<String> = std::env::args().collect();
+    // keep stdout clean for `--export`
+    let exporting = args.iter().any(|arg| arg == "--export");
+
     let num_valid = calc_part_two(FULL);
-    println!("Found {}", num_valid);
+    if !exporting {
+        println!("Found {}", num_valid);
+    }
+    // `--schema rules.toml` (or `.json`) swaps in another jurisdiction's rules
+    let schema = match args.iter().position(|arg| arg == "--schema") {
+        Some(i) => {
+            let path = match args.get(i + 1) {
+                Some(path) => path,
+                None => {
+                    eprintln!("--schema needs a path");
+                    std::process::exit(1);
+                }
+            };
+            let schema = match Schema::load(path.as_ref()) {
+                Ok(schema) => schema,
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }
+            };
+            if !exporting {
+                println!("Found {} under {}", count_valid(FULL, &schema), path);
+            }
+            schema
+        }
+        None => Schema::builtin().clone(),
+    };
+
+    if args.iter().any(|arg| arg == "--report") {
+        for (i, problems) in problems(FULL, &schema) {
+            println!("passport {}:", i);
+            for problem in problems {
+                println!("    {}", problem);
+            }
+        }
+    }
+
+    // `--stream passports.txt` validates a batch of any size, or `--stream -` for stdin
+    if let Some(i) = args.iter().position(|arg| arg == "--stream") {
+        let path = match args.get(i + 1) {
+            Some(path) => path,
+            None => {
+                eprintln!("--stream needs a path, or - for stdin");
+                std::process::exit(1);
+            }
+        };
+        let reader: Box<dyn std::io::BufRead> = match path.as_str() {
+            "-" => Box::new(std::io::stdin().lock()),
+            path => match std::fs::File::open(path) {
+                Ok(file) => Box::new(std::io::BufReader::new(file)),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }
+            },
+        };
+        let mut summary = batch::Summary::default();
+        for record in batch::Records::new(reader) {
+            match record {
+                Ok(record) => {
+                    summary.add(&record, &schema);
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        print!("{}", summary);
+    }
+
+    // `--query "ecl=blu and byr<1950"` prints matching passports, or with `--count` just how
+    // many, or with `--histogram hgt.unit` how many of each value
+    if let Some(i) = args.iter().position(|arg| arg == "--query") {
+        let source = match args.get(i + 1) {
+            Some(source) => source,
+            None => {
+                eprintln!("--query needs a query, like \"ecl=blu and byr<1950\"");
+                std::process::exit(1);
+            }
+        };
+        let expr = match query::parse(source) {
+            Ok(expr) => expr,
+            Err(e) => {
+                eprintln!("bad query {:?}: {}", source, e);
+                std::process::exit(1);
+            }
+        };
+        let owned: Vec<OwnedPassport> = valid_passports(FULL, &schema)
+            .into_iter()
+            .filter(|p| expr.matches(&p.as_passport()))
+            .collect();
+
+        if args.iter().any(|arg| arg == "--count") {
+            println!("{} passports match", owned.len());
+        } else if let Some(j) = args.iter().position(|arg| arg == "--histogram") {
+            let field = match args
+                .get(j + 1)
+                .map(|field| (field, query::parse_field(field)))
+            {
+                Some((_, Ok(field))) => field,
+                Some((field, Err(e))) => {
+                    eprintln!("bad --histogram field {:?}: {}", field, e);
+                    std::process::exit(1);
+                }
+                None => {
+                    eprintln!("--histogram needs a field, like hgt.unit");
+                    std::process::exit(1);
+                }
+            };
+            let passports: Vec<Passport> = owned.iter().map(OwnedPassport::as_passport).collect();
+            for (value, count) in query::histogram(&passports, &field) {
+                println!("{:>12} {:>4} {}", value, count, "#".repeat(count));
+            }
+        } else if let Err(e) = batch::write_batch(&owned, std::io::stdout().lock()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--cross-check") {
+        let passports = valid_passports(FULL, &schema);
+        for finding in consistency::check_batch(&passports, &Default::default()) {
+            println!("{}", finding);
+        }
+    }
+
+    // `--export json`, `--export csv` or `--export batch` writes out every valid passport
+    if let Some(i) = args.iter().position(|arg| arg == "--export") {
+        let passports = valid_passports(FULL, &schema);
+        let out = std::io::stdout().lock();
+        let written = match args.get(i + 1).map(String::as_str) {
+            Some("json") => batch::write_json_lines(&passports, out),
+            Some("csv") => batch::write_csv(&passports, out).map_err(Into::into),
+            Some("batch") => batch::write_batch(&passports, out),
+            other => {
+                eprintln!("--export takes json, csv or batch, not {:?}", other);
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = written {
+            eprintln!("export failed: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 const EXAMPLE: &str = r#"ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
@@ -259,22 +1614,631 @@ mod tests {
 
     #[test]
     fn test_builder() {
+        // missing fields are the schema's business, found while parsing
         assert!(PassportBuilder {
             ..Default::default()
         }
         .build()
-        .is_err());
+        .is_ok());
+        match PassportBuilder::parse("").unwrap().build() {
+            Err(Error::Invalid(problems)) => {
+                assert_eq!(problems.len(), 7);
+                assert!(problems.iter().all(|p| p.kind == ProblemKind::Missing));
+            }
+            other => panic!("expected missing fields, got {:?}", other),
+        }
         assert!(PassportBuilder {
             birth_year: Some(Year(2014)),
             issue_year: Some(Year(2017)),
             expiration_year: Some(Year(2023)),
-            height: Some(Length::Cm(195)),
+            height: Some(Length::new(195.0, Unit::Cm)),
             hair_color: Some(Color("#ffffff")),
             eye_color: Some(Color("#ee7812")),
             passport_id: Some(ID("00023437")),
             country_id: None,
+            extra: vec![],
+            problems: vec![],
         }
         .build()
         .is_ok());
     }
+
+    #[test]
+    fn test_every_problem() {
+        let input = "byr:1900 hgt:180yd\nhcl:123abc ecl:red foo:bar pid:0123 byr:1990";
+        let problems = match PassportBuilder::parse(input).unwrap().build() {
+            Err(Error::Invalid(problems)) => problems,
+            other => panic!("expected problems, got {:?}", other),
+        };
+        let problem = |kind, key, raw, span| Problem {
+            kind,
+            key,
+            raw,
+            span: Some(span),
+        };
+        let missing = |key| Problem {
+            kind: ProblemKind::Missing,
+            key,
+            raw: "",
+            span: None,
+        };
+        assert_eq!(
+            problems,
+            vec![
+                problem(ProblemKind::OutOfRange, "byr", "1900", 0..8),
+                problem(ProblemKind::BadUnit, "hgt", "180yd", 9..18),
+                problem(ProblemKind::MalformedColor, "hcl", "123abc", 19..29),
+                problem(ProblemKind::NotAllowed, "ecl", "red", 30..37),
+                problem(ProblemKind::UnknownKey, "foo", "bar", 38..45),
+                problem(ProblemKind::MalformedId, "pid", "0123", 46..54),
+                missing("iyr"),
+                missing("eyr"),
+            ]
+        );
+        // the later, valid birth year still counts
+        assert_eq!(&input[55..63], "byr:1990");
+        assert_eq!(
+            problems[0].to_string(),
+            "byr:1900 (bytes 0..8): out of range"
+        );
+        assert_eq!(problems[7].to_string(), "eyr: missing");
+    }
+
+    #[test]
+    fn test_duplicate_and_malformed() {
+        let input = "byr:1950 byr:1960 hello";
+        let b = PassportBuilder::parse(input).unwrap();
+        assert_eq!(b.birth_year, Some(Year(1950)));
+        let kinds: Vec<_> = b.problems.iter().map(|p| p.kind).collect();
+        assert_eq!(kinds[..2], [ProblemKind::Duplicate, ProblemKind::Malformed]);
+        assert!(kinds[2..].iter().all(|&kind| kind == ProblemKind::Missing));
+        assert_eq!(b.problems[1].span, Some(18..23));
+    }
+
+    #[test]
+    fn test_problems_example() {
+        // two example passports are invalid: the second is missing its height, the last its
+        // birth year
+        let found = problems(EXAMPLE, Schema::builtin());
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, 1);
+        assert_eq!(found[0].1.len(), 1);
+        assert_eq!(found[0].1[0].key, "hgt");
+        assert_eq!(found[1].0, 3);
+        assert_eq!(found[1].1[0].key, "byr");
+        assert_eq!(
+            problems(FULL, Schema::builtin()).len(),
+            FULL.split("\n\n").count() - 116
+        );
+    }
+
+    #[test]
+    fn test_builtin_schema_round_trip() {
+        let builtin = Schema::builtin();
+        assert_eq!(builtin.fields.len(), 8);
+        let json = serde_json::to_string(builtin).unwrap();
+        assert_eq!(&Schema::from_json(&json).unwrap(), builtin);
+    }
+
+    #[test]
+    fn test_custom_schema() {
+        let custom = r#"
+            [[field]]
+            key = "byr"
+            type = "year"
+            min = 1900
+            max = 2020
+
+            [[field]]
+            key = "iyr"
+            type = "year"
+            min = 2000
+            max = 2030
+
+            [[field]]
+            key = "eyr"
+            type = "year"
+            min = 2000
+            max = 2040
+
+            [[field]]
+            key = "hgt"
+            type = "length"
+            unit = "cm"
+            min = 100
+            max = 250
+
+            [[field]]
+            key = "hcl"
+            type = "enum"
+            values = ["black", "brown", "blond"]
+
+            [[field]]
+            key = "ecl"
+            type = "hex_color"
+
+            [[field]]
+            key = "pid"
+            type = "digits"
+            width = 6
+
+            [[field]]
+            key = "cid"
+            type = "digits"
+            width = 3
+
+            [[field]]
+            key = "nat"
+            type = "text"
+            required = false
+        "#;
+        let path =
+            std::env::temp_dir().join(format!("day-04-test-schema-{}.toml", std::process::id()));
+        std::fs::write(&path, custom).unwrap();
+        let schema = Schema::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let passport = "byr:1910 iyr:2001 eyr:2035 hgt:220cm hcl:blond ecl:#123abc pid:123456 \
+            cid:042 nat:NZ";
+        let passport = PassportBuilder::parse_with(passport, &schema)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(passport.height, Some(Length::new(220.0, Unit::Cm)));
+        assert_eq!(passport.country_id, Some(ID("042")));
+        assert_eq!(passport.extra, [("nat", "NZ")]);
+
+        let kinds = |input| match PassportBuilder::parse_with(input, &schema).unwrap().build() {
+            Err(Error::Invalid(problems)) => problems
+                .into_iter()
+                .map(|p| (p.key, p.kind))
+                .collect::<Vec<_>>(),
+            other => panic!("expected problems, got {:?}", other),
+        };
+        assert_eq!(
+            kinds("byr:1910 iyr:2001 eyr:2035 hgt:70yd hcl:red ecl:#123abc pid:123456789"),
+            [
+                ("hgt", ProblemKind::BadUnit),
+                ("hcl", ProblemKind::NotAllowed),
+                ("pid", ProblemKind::MalformedId),
+                ("cid", ProblemKind::Missing),
+            ]
+        );
+
+        // the puzzle's passports don't fare well here
+        assert_eq!(count_valid(EXAMPLE, &schema), 0);
+    }
+
+    #[test]
+    fn test_unusable_schema() {
+        let builtin = Schema::builtin();
+        let unusable = |schema: Schema| {
+            let json = serde_json::to_string(&schema).unwrap();
+            match Schema::from_json(&json) {
+                Err(schema::SchemaError::Unusable(msg)) => msg,
+                other => panic!("expected an unusable schema, got {:?}", other),
+            }
+        };
+
+        let mut schema = builtin.clone();
+        schema.fields[3].kind = builtin.fields[0].kind.clone();
+        assert!(unusable(schema).starts_with("hgt can't be a Year"));
+
+        let mut schema = builtin.clone();
+        schema.fields.push(builtin.fields[0].clone());
+        assert_eq!(unusable(schema), "byr is declared twice");
+
+        // the puzzle's keys can be optional, or left out entirely
+        let mut schema = builtin.clone();
+        schema.fields.retain(|rule| rule.key != "pid");
+        schema.fields[0].required = false;
+        let json = serde_json::to_string(&schema).unwrap();
+        let schema = Schema::from_json(&json).unwrap();
+        let passport =
+            PassportBuilder::parse_with("iyr:2015 eyr:2025 hgt:170cm hcl:#123abc ecl:brn", &schema)
+                .unwrap()
+                .build()
+                .unwrap();
+        assert_eq!(passport.birth_year, None);
+        assert_eq!(passport.passport_id, None);
+
+        assert!(matches!(
+            Schema::from_toml("[[field]]\nkey = \"byr\"\ntype = \"date\""),
+            Err(schema::SchemaError::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let schema = Schema::builtin();
+        let passports = valid_passports(FULL, schema);
+        assert_eq!(passports.len(), 116);
+        // written out, they're numbered afresh
+        let renumbered: Vec<OwnedPassport> = passports
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(record, p)| OwnedPassport { record, ..p })
+            .collect();
+
+        let mut out = vec![];
+        batch::write_batch(&passports, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(batch::read_batch(&text, schema).unwrap(), renumbered);
+        // it's still the puzzle's format, too
+        assert_eq!(calc_part_two(&text), 116);
+
+        let mut out = vec![];
+        batch::write_json_lines(&passports, &mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert_eq!(json.lines().count(), 116);
+        assert_eq!(batch::read_json_lines(&json, schema).unwrap(), renumbered);
+
+        let mut out = vec![];
+        batch::write_csv(&passports, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(csv.lines().count(), 117);
+        assert_eq!(batch::read_csv(&csv, schema).unwrap(), renumbered);
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let passports = valid_passports(EXAMPLE, Schema::builtin());
+        let mut first = passports[0].clone();
+        first.extra.insert("nat".into(), "NZ".into());
+
+        let mut out = vec![];
+        batch::write_json_lines(&[first.clone()], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r##"{"birth_year":1937,"issue_year":2017,"expiration_year":2020,"height":"183cm","hair_color":"#fffffd","eye_color":"gry","passport_id":"860033327","country_id":"147","extra":{"nat":"NZ"}}"##
+                .to_owned()
+                + "\n"
+        );
+
+        let mut out = vec![];
+        batch::write_csv(&[first.clone(), passports[1].clone()], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "birth_year,issue_year,expiration_year,height,hair_color,eye_color,passport_id,country_id,extra\n\
+             1937,2017,2020,183cm,#fffffd,gry,860033327,147,nat:NZ\n\
+             1931,2013,2024,179cm,#ae17e1,brn,760753108,,\n"
+        );
+
+        let mut out = vec![];
+        batch::write_batch(&[first, passports[1].clone()], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "byr:1937 iyr:2017 eyr:2020 hgt:183cm hcl:#fffffd ecl:gry pid:860033327 cid:147 nat:NZ\n\
+             \n\
+             byr:1931 iyr:2013 eyr:2024 hgt:179cm hcl:#ae17e1 ecl:brn pid:760753108\n"
+        );
+
+        // `nat` isn't in the builtin schema
+        match batch::read_batch(
+            "byr:1937 iyr:2017 eyr:2020 hgt:183cm hcl:#fffffd ecl:gry pid:860033327 nat:NZ",
+            Schema::builtin(),
+        ) {
+            Err(batch::ImportError::Invalid { record, problems }) => {
+                assert_eq!(record, 0);
+                assert_eq!(problems, ["nat:NZ (bytes 71..77): unknown key"]);
+            }
+            other => panic!("expected an invalid record, got {:?}", other),
+        }
+
+        // JSON gets the same checks
+        let json = r##"{"birth_year":1937,"issue_year":2017,"expiration_year":2020,"height":"183cm","hair_color":"#fffffd","eye_color":"gry","passport_id":"860033327"}
+{"birth_year":1937,"issue_year":2017,"expiration_year":2020,"height":"183cm","hair_color":"red","eye_color":"gry"}"##;
+        match batch::read_json_lines(json, Schema::builtin()) {
+            Err(batch::ImportError::Invalid { record, problems }) => {
+                assert_eq!(record, 1);
+                assert_eq!(problems.len(), 2);
+            }
+            other => panic!("expected an invalid record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_length_notations() {
+        let cm = |value| Length::new(value, Unit::Cm);
+        let inches = |value| Length::new(value, Unit::In);
+        for (notation, expected) in [
+            ("183cm", cm(183.0)),
+            ("180 cm", cm(180.0)),
+            ("1.80m", cm(180.0)),
+            ("1805mm", cm(180.5)),
+            ("1502mm", cm(150.2)),
+            ("1.1m", cm(110.0)),
+            ("1.5ft", inches(18.0)),
+            ("5'1.5\"", inches(61.5)),
+            ("59in", inches(59.0)),
+            ("71\"", inches(71.0)),
+            ("5'11\"", inches(71.0)),
+            ("6'", inches(72.0)),
+            ("6ft", inches(72.0)),
+        ] {
+            assert_eq!(notation.parse::<Length>(), Ok(expected), "{}", notation);
+        }
+        for bad in ["180", "180yd", "cm", "5'11", "1.cm", ""] {
+            assert!(bad.parse::<Length>().is_err(), "{}", bad);
+        }
+
+        assert!((inches(71.0).to(Unit::Cm).value - 180.34).abs() < 1e-9);
+        assert!((cm(254.0).to(Unit::In).value - 100.0).abs() < 1e-9);
+        assert_eq!(cm(180.5).to_string(), "180.5cm");
+        assert_eq!("1502mm".parse::<Length>().unwrap().to_string(), "150.2cm");
+        assert_eq!("1.1m".parse::<Length>().unwrap().to_string(), "110cm");
+        assert_eq!(inches(71.0).to_string(), "71in");
+    }
+
+    #[test]
+    fn test_height_tolerance() {
+        let height = |raw| {
+            let b = PassportBuilder::parse(raw).unwrap();
+            b.height.ok_or_else(|| b.problems[0].kind)
+        };
+        // half a centimetre either way lets the puzzle's inch bounds through, and nothing else
+        assert_eq!(height("hgt:59in"), Ok(Length::new(59.0, Unit::In)));
+        assert_eq!(height("hgt:76in"), Ok(Length::new(76.0, Unit::In)));
+        assert_eq!(height("hgt:58in"), Err(ProblemKind::OutOfRange));
+        assert_eq!(height("hgt:77in"), Err(ProblemKind::OutOfRange));
+        assert_eq!(height("hgt:149cm"), Err(ProblemKind::OutOfRange));
+        assert_eq!(height("hgt:194cm"), Err(ProblemKind::OutOfRange));
+        assert_eq!(height("hgt:1.93m"), Ok(Length::new(193.0, Unit::Cm)));
+        assert_eq!(height("hgt:5'11\""), Ok(Length::new(71.0, Unit::In)));
+        assert_eq!(height("hgt:tall"), Err(ProblemKind::NotANumber));
+
+        // `180 cm` is one field, not a field and some rubbish
+        let b = PassportBuilder::parse("hgt:180 cm\nbyr:1980").unwrap();
+        assert_eq!(b.height, Some(Length::new(180.0, Unit::Cm)));
+        assert_eq!(b.birth_year, Some(Year(1980)));
+        assert!(b.problems.iter().all(|p| p.kind == ProblemKind::Missing));
+
+        let mut strict = Schema::builtin().clone();
+        for rule in &mut strict.fields {
+            if let schema::FieldType::Length { tolerance, .. } = &mut rule.kind {
+                *tolerance = 0.0;
+            }
+        }
+        let b = PassportBuilder::parse_with("hgt:59in", &strict).unwrap();
+        assert_eq!(b.problems[0].kind, ProblemKind::OutOfRange);
+    }
+
+    #[test]
+    fn test_cross_checks() {
+        use consistency::{check_batch, Finding, FindingKind, Limits};
+
+        let passports = batch::read_batch(
+            "byr:1980 iyr:2015 eyr:2025 hgt:180cm hcl:#123abc ecl:blu pid:000000001 cid:1\n\
+             \n\
+             byr:2002 iyr:2020 eyr:2020 hgt:180cm hcl:#123abc ecl:blu pid:000000002\n\
+             \n\
+             byr:1980 iyr:2010 eyr:2030 hgt:70in hcl:#123abc ecl:grn pid:000000001 cid:1\n\
+             \n\
+             byr:1920 iyr:2020 eyr:2022 hgt:180cm hcl:#123abc ecl:blu pid:000000003 cid:1\n\
+             \n\
+             byr:1990 iyr:2012 eyr:2021 hgt:180cm hcl:#123abc ecl:amb pid:000000004 cid:2",
+            Schema::builtin(),
+        )
+        .unwrap();
+
+        let findings = check_batch(&passports, &Limits::default());
+        let finding = |records: &[usize], kind| Finding {
+            records: records.to_vec(),
+            kind,
+        };
+        assert_eq!(
+            findings,
+            [
+                finding(&[2], FindingKind::TooLongValid { years: 20 }),
+                finding(
+                    &[0, 2],
+                    FindingKind::DuplicatePassportId {
+                        passport_id: "000000001".into()
+                    }
+                ),
+                finding(
+                    &[0, 2, 3],
+                    FindingKind::ConflictingCountryId {
+                        country_id: "1".into(),
+                        fields: vec!["birth_year", "eye_color"]
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            findings[2].to_string(),
+            "passport 0, 2, 3: share country id 1 but not birth_year, eye_color"
+        );
+
+        // a hundred is old, but not too old by default; findings keep their record numbers
+        let strict = Limits {
+            age_at_issue: (16, 90),
+            validity: 20,
+        };
+        let findings = check_batch(&passports[3..], &strict);
+        assert_eq!(
+            findings,
+            [finding(&[3], FindingKind::ImplausibleAge { age: 100 })]
+        );
+
+        // running out the year it's issued is fine (as passport 1 does), but not any earlier
+        let mut expired = passports[4].clone();
+        expired.expiration_year = Some(Year(2011));
+        assert_eq!(
+            consistency::check_passport(&expired, &strict),
+            [finding(
+                &[4],
+                FindingKind::ExpiresBeforeIssue {
+                    issued: Year(2012),
+                    expires: Year(2011)
+                }
+            )]
+        );
+
+        let mut unborn = passports[4].clone();
+        unborn.issue_year = Some(Year(1980));
+        assert_eq!(
+            consistency::check_passport(&unborn, &strict)[0].kind,
+            FindingKind::IssuedBeforeBirth {
+                born: Year(1990),
+                issued: Year(1980)
+            }
+        );
+    }
+
+    #[test]
+    fn test_streaming_records() {
+        let records = |input: &str| {
+            batch::Records::new(input.as_bytes())
+                .collect::<std::io::Result<Vec<_>>>()
+                .unwrap()
+        };
+
+        let messy = "\r\n\r\nbyr:1937 iyr:2017  \r\ncid:147\t\r\n\r\n \r\n\n\
+                     pid:1\r\n\r\n\r\n";
+        let found = records(messy);
+        assert_eq!(
+            found,
+            [
+                batch::Record {
+                    index: 0,
+                    line: 3,
+                    text: "byr:1937 iyr:2017\ncid:147".into()
+                },
+                batch::Record {
+                    index: 1,
+                    line: 8,
+                    text: "pid:1".into()
+                },
+            ]
+        );
+        assert_eq!(records(""), []);
+        assert_eq!(records("pid:1").len(), 1);
+
+        // the same answers as splitting the whole thing up front, with or without CRLF
+        for input in [FULL.to_owned(), FULL.replace('\n', "\r\n")] {
+            let mut summary = batch::Summary::default();
+            let mut passports = vec![];
+            for record in
+                batch::Records::new(std::io::BufReader::with_capacity(64, input.as_bytes()))
+            {
+                passports.extend(summary.add(&record.unwrap(), Schema::builtin()));
+            }
+            assert_eq!(summary.records, FULL.split("\n\n").count());
+            assert_eq!(summary.valid, 116);
+            assert_eq!(passports, valid_passports(FULL, Schema::builtin()));
+            let counted: usize = summary.problems.values().sum();
+            let expected: usize = problems(FULL, Schema::builtin())
+                .iter()
+                .map(|(_, problems)| problems.len())
+                .sum();
+            assert_eq!(counted, expected);
+        }
+
+        let mut long = batch::Records::new("byr:1937 iyr:2017".as_bytes()).max_record_len(8);
+        assert_eq!(
+            long.next().unwrap().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        assert!(long.next().is_none());
+
+        // a record that's too long is skipped, and the ones after it still come through
+        let input = format!(
+            "pid:1\n\n{}\nbyr:1937\n\n\nbyr:1937\niyr:2017\n\niyr:2017\n",
+            "x".repeat(1 << 20)
+        );
+        let mut long = batch::Records::new(input.as_bytes()).max_record_len(12);
+        assert_eq!(long.next().unwrap().unwrap().text, "pid:1");
+        let error = long.next().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "record 1 (line 3) is over 12 bytes");
+        let error = long.next().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "record 2 (line 7) is over 12 bytes");
+        assert_eq!(
+            long.next().unwrap().unwrap(),
+            batch::Record {
+                index: 3,
+                line: 10,
+                text: "iyr:2017".into()
+            }
+        );
+        assert!(long.next().is_none());
+
+        // and so is one that isn't UTF-8
+        let input = b"pid:1\nbyr:\xff\nhgt:1\n\niyr:2017\n";
+        let mut bad = batch::Records::new(&input[..]);
+        let error = bad.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error
+            .to_string()
+            .starts_with("record 0 (line 1) isn't UTF-8"));
+        assert_eq!(
+            bad.next().unwrap().unwrap(),
+            batch::Record {
+                index: 1,
+                line: 5,
+                text: "iyr:2017".into()
+            }
+        );
+        assert!(bad.next().is_none());
+    }
+
+    #[test]
+    fn test_query() {
+        let owned = batch::read_batch(
+            "byr:1940 iyr:2015 eyr:2025 hgt:70in hcl:#123abc ecl:blu pid:000000001 cid:1\n\
+             \n\
+             byr:1960 iyr:2015 eyr:2025 hgt:180cm hcl:#123abc ecl:blu pid:000000002\n\
+             \n\
+             byr:1930 iyr:2015 eyr:2025 hgt:5'9\" hcl:#fedcba ecl:brn pid:000000003 cid:2\n\
+             \n\
+             byr:1945 iyr:2015 eyr:2025 hgt:1.9m hcl:#123abc ecl:blu pid:000000004",
+            Schema::builtin(),
+        )
+        .unwrap();
+        let passports: Vec<Passport> = owned.iter().map(OwnedPassport::as_passport).collect();
+        let matching = |source: &str| {
+            let expr = query::parse(source).unwrap();
+            passports
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| expr.matches(p))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching("ecl=blu and byr<1950 and hgt.unit=in"), [0]);
+        assert_eq!(matching("ecl=blu and byr<1950"), [0, 3]);
+        assert_eq!(matching("hgt.unit = in or hgt > 185"), [0, 2, 3]);
+        assert_eq!(matching("hgt.in >= 70"), [0, 1, 3]);
+        assert_eq!(matching("hgt.value=70"), [0]);
+        assert!(matching("not (ecl=blu or cid=2)").is_empty());
+        assert_eq!(matching("not ecl=blu"), [2]);
+        // `and` binds tighter than `or`
+        assert_eq!(matching("byr<1935 or byr>1950 and ecl=blu"), [1, 2]);
+        assert_eq!(matching("(byr<1935 or byr>1950) and ecl=blu"), [1]);
+        // no `cid` never matches, not even `!=`
+        assert_eq!(matching("cid!=1"), [2]);
+        assert_eq!(matching("hcl>=#fe"), [2]);
+
+        for bad in [
+            "ecl",
+            "byr<old",
+            "ecl.unit=cm",
+            "ecl=blu and",
+            "(ecl=blu",
+            "ecl=blu nor byr=1",
+        ] {
+            assert!(query::parse(bad).is_err(), "{}", bad);
+        }
+
+        let field = query::parse_field("hgt.unit").unwrap();
+        let histogram = query::histogram(&passports, &field);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [("cm".to_owned(), 2), ("in".to_owned(), 2)]
+        );
+        let field = query::parse_field("cid").unwrap();
+        assert_eq!(query::histogram(&passports, &field).len(), 2);
+    }
 }
@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use day_05_binary_boarding::{Batch, Bits, Decoder, Seat, ShiftMask};
+
+const PASSES: usize = 2_000_000;
+
+// A deterministic file of passes: ids from a small LCG, so every run benches the same input.
+fn generate(n: usize) -> String {
+    let mut state: u64 = 0x2020;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let seat = Seat::from_id((state >> 33) as u16 % 1024).unwrap();
+            format!("{}\n", seat)
+        })
+        .collect()
+}
+
+fn bench<D: Decoder>(
+    group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+    input: &str,
+) {
+    group.bench_with_input(BenchmarkId::new(D::NAME, PASSES), input, |b, input| {
+        b.iter(|| D::decode_all(black_box(input)).unwrap())
+    });
+}
+
+fn decode_all(c: &mut Criterion) {
+    let input = generate(PASSES);
+
+    let mut group = c.benchmark_group("decode_all");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(PASSES as u64));
+    bench::<Bits>(&mut group, &input);
+    bench::<ShiftMask>(&mut group, &input);
+    bench::<Batch>(&mut group, &input);
+    group.finish();
+}
+
+criterion_group!(benches, decode_all);
+criterion_main!(benches);
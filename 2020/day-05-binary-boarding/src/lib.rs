@@ -0,0 +1,584 @@
+use bitvec::prelude::*;
+use codec::Codec;
+use std::str::FromStr;
+
+pub use codec::Error;
+
+/// A seat on the puzzle's plane: seven row letters (`F`/`B`) and three column letters (`L`/`R`).
+///
+/// The pass is just the seat id in binary, so that's all this stores.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Seat(u16);
+
+impl Seat {
+    pub const ROW_BITS: usize = 7;
+    pub const COL_BITS: usize = 3;
+    pub const LEN: usize = Self::ROW_BITS + Self::COL_BITS;
+    pub const ROWS: u16 = 1 << Self::ROW_BITS;
+    pub const COLS: u16 = 1 << Self::COL_BITS;
+
+    pub fn new(row: u16, col: u16) -> Option<Self> {
+        (row < Self::ROWS && col < Self::COLS).then_some(Seat(row << Self::COL_BITS | col))
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        (id < Self::ROWS * Self::COLS).then_some(Seat(id))
+    }
+
+    pub fn row(self) -> u16 {
+        self.0 >> Self::COL_BITS
+    }
+
+    pub fn col(self) -> u16 {
+        self.0 & (Self::COLS - 1)
+    }
+
+    pub fn id(self) -> u16 {
+        self.0
+    }
+
+    pub fn parse(pass: &str) -> Result<Self, Error> {
+        Self::parse_with::<ShiftMask>(pass)
+    }
+
+    pub fn parse_with<D: Decoder>(pass: &str) -> Result<Self, Error> {
+        D::decode(pass)
+    }
+}
+
+impl FromStr for Seat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// The pass for this seat.
+impl std::fmt::Display for Seat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pass = Codec::AOC
+            .encode_id(self.0.into())
+            .expect("every seat has a pass");
+        write!(f, "{}", pass)
+    }
+}
+
+/// An [`Error`] in a whole file of passes.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("line {line}: {error}")]
+pub struct LineError {
+    /// From 1
+    pub line: usize,
+    pub error: Error,
+}
+
+/// The zero and one letters at `position` in a pass, as bytes.
+fn letters(position: usize) -> (u8, u8) {
+    let alphabet = Codec::AOC.alphabet(position);
+    (alphabet.zero as u8, alphabet.one as u8)
+}
+
+/// A way of turning passes into seats. They all check passes with [`Codec::AOC`], so they
+/// agree on every error; they differ in how they add up the bits, and so in speed.
+pub trait Decoder {
+    const NAME: &'static str;
+
+    fn decode(pass: &str) -> Result<Seat, Error>;
+
+    /// One pass per line, stopping at the first bad one.
+    fn decode_all(input: &str) -> Result<Vec<Seat>, LineError> {
+        input
+            .lines()
+            .enumerate()
+            .map(|(i, pass)| Self::decode(pass).map_err(|error| LineError { line: i + 1, error }))
+            .collect()
+    }
+}
+
+/// Sets one bit per letter, as `part_one` and `part_two` used to.
+pub struct Bits;
+
+impl Decoder for Bits {
+    const NAME: &'static str = "bits";
+
+    fn decode(pass: &str) -> Result<Seat, Error> {
+        Codec::AOC.check(pass)?;
+        let mut id = 0u16;
+        let bits = BitSlice::<Msb0, _>::from_element_mut(&mut id);
+        // the pass fills the low ten of sixteen bits
+        let offset = 16 - Seat::LEN;
+        for (i, &b) in pass.as_bytes().iter().enumerate() {
+            bits.set(offset + i, b == letters(i).1);
+        }
+        Ok(Seat(id))
+    }
+}
+
+/// [`Codec::AOC`] itself, which shifts each letter into an integer.
+pub struct ShiftMask;
+
+impl Decoder for ShiftMask {
+    const NAME: &'static str = "shift-mask";
+
+    fn decode(pass: &str) -> Result<Seat, Error> {
+        let decoded = Codec::AOC.decode(pass)?;
+        Ok(Seat(decoded.id as u16))
+    }
+}
+
+/// Decodes a whole file at once when every line is ten letters and a `\n`, which is the usual
+/// case. Each pass is a fixed-size block decoded without branching on its letters, which
+/// `benches/decoders.rs` puts at over ten times faster than [`ShiftMask`]. Anything else,
+/// including a bad pass, is handed to [`ShiftMask`] line by line.
+pub struct Batch;
+
+impl Batch {
+    /// The id, and whether every letter was valid.
+    fn decode_block(pass: &[u8; Seat::LEN]) -> (u16, bool) {
+        let mut id = 0;
+        let mut valid = true;
+        for (i, &b) in pass.iter().enumerate() {
+            let (zero, one) = letters(i);
+            valid &= (b == zero) | (b == one);
+            id = id << 1 | (b == one) as u16;
+        }
+        (id, valid)
+    }
+}
+
+impl Decoder for Batch {
+    const NAME: &'static str = "batch";
+
+    fn decode(pass: &str) -> Result<Seat, Error> {
+        ShiftMask::decode(pass)
+    }
+
+    fn decode_all(input: &str) -> Result<Vec<Seat>, LineError> {
+        let bytes = input.as_bytes();
+        let stride = Seat::LEN + 1;
+        let mut seats = Vec::with_capacity(bytes.len() / stride + 1);
+        let mut valid = true;
+        for block in bytes.chunks(stride) {
+            let (pass, newline) = block.split_at(Seat::LEN.min(block.len()));
+            let (id, ok) = match pass.try_into() {
+                Ok(pass) => Self::decode_block(pass),
+                Err(_) => (0, false),
+            };
+            valid &= ok & newline.iter().all(|&b| b == b'\n');
+            seats.push(Seat(id));
+        }
+        match valid {
+            true => Ok(seats),
+            false => ShiftMask::decode_all(input),
+        }
+    }
+}
+
+/// Boarding passes for any plane: some bits of row letters, then some bits of column letters.
+pub mod codec {
+    use std::str::FromStr;
+
+    /// The letters meaning 0 and 1 for one half of a pass, like `F` and `B` for rows.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Alphabet {
+        pub zero: char,
+        pub one: char,
+    }
+
+    impl Alphabet {
+        pub const fn new(zero: char, one: char) -> Self {
+            Self { zero, one }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Codec {
+        row_bits: u32,
+        col_bits: u32,
+        rows: Alphabet,
+        cols: Alphabet,
+    }
+
+    /// Where a pass puts you.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Decoded {
+        pub row: u64,
+        pub col: u64,
+        pub id: u64,
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+    pub enum Error {
+        #[error("layouts need at least one bit per half and fewer than 64 in all, and two different letters for each")]
+        BadLayout,
+
+        #[error("expected {expected} letters but got {found}")]
+        WrongLength { expected: usize, found: usize },
+
+        #[error("unexpected letter {found:?} at position {position}")]
+        InvalidChar { position: usize, found: char },
+
+        #[error("{what} {value} is out of range, the maximum is {max}")]
+        OutOfRange {
+            what: &'static str,
+            value: u64,
+            max: u64,
+        },
+    }
+
+    impl Codec {
+        /// The puzzle's plane: 128 rows of 8 seats, with `FFFFFFFLLL` at the front left.
+        pub const AOC: Codec = Codec {
+            row_bits: 7,
+            col_bits: 3,
+            rows: Alphabet::new('F', 'B'),
+            cols: Alphabet::new('L', 'R'),
+        };
+
+        pub fn new(
+            row_bits: u32,
+            col_bits: u32,
+            rows: Alphabet,
+            cols: Alphabet,
+        ) -> Result<Self, Error> {
+            let bits_ok = row_bits > 0
+                && col_bits > 0
+                && row_bits.checked_add(col_bits).is_some_and(|bits| bits < 64);
+            if !bits_ok || rows.zero == rows.one || cols.zero == cols.one {
+                return Err(Error::BadLayout);
+            }
+            Ok(Self {
+                row_bits,
+                col_bits,
+                rows,
+                cols,
+            })
+        }
+
+        /// Letters in a pass
+        pub fn pass_len(&self) -> usize {
+            (self.row_bits + self.col_bits) as usize
+        }
+
+        pub fn rows(&self) -> u64 {
+            1 << self.row_bits
+        }
+
+        pub fn cols(&self) -> u64 {
+            1 << self.col_bits
+        }
+
+        /// Every id this layout has, from front left to back right
+        pub fn seats(&self) -> u64 {
+            self.rows() * self.cols()
+        }
+
+        pub fn id(&self, row: u64, col: u64) -> u64 {
+            (row << self.col_bits) | col
+        }
+
+        /// The letters allowed at `position` in a pass.
+        pub fn alphabet(&self, position: usize) -> Alphabet {
+            match position < self.row_bits as usize {
+                true => self.rows,
+                false => self.cols,
+            }
+        }
+
+        /// Whether `pass` is one of this layout's passes: the length first, then each letter.
+        pub fn check(&self, pass: &str) -> Result<(), Error> {
+            let found = pass.chars().count();
+            if found != self.pass_len() {
+                return Err(Error::WrongLength {
+                    expected: self.pass_len(),
+                    found,
+                });
+            }
+            for (position, found) in pass.chars().enumerate() {
+                let alphabet = self.alphabet(position);
+                if found != alphabet.zero && found != alphabet.one {
+                    return Err(Error::InvalidChar { position, found });
+                }
+            }
+            Ok(())
+        }
+
+        pub fn decode(&self, pass: &str) -> Result<Decoded, Error> {
+            self.check(pass)?;
+            let id = pass.chars().enumerate().fold(0, |id, (position, c)| {
+                id << 1 | (c == self.alphabet(position).one) as u64
+            });
+            Ok(Decoded {
+                row: id >> self.col_bits,
+                col: id & (self.cols() - 1),
+                id,
+            })
+        }
+
+        pub fn encode(&self, row: u64, col: u64) -> Result<String, Error> {
+            let check = |what, value, count: u64| match value < count {
+                true => Ok(()),
+                false => Err(Error::OutOfRange {
+                    what,
+                    value,
+                    max: count - 1,
+                }),
+            };
+            check("row", row, self.rows())?;
+            check("column", col, self.cols())?;
+
+            let letters = |value: u64, bits: u32, alphabet: Alphabet| {
+                (0..bits).rev().map(move |i| match (value >> i) & 1 {
+                    0 => alphabet.zero,
+                    _ => alphabet.one,
+                })
+            };
+            Ok(letters(row, self.row_bits, self.rows)
+                .chain(letters(col, self.col_bits, self.cols))
+                .collect())
+        }
+
+        pub fn encode_id(&self, id: u64) -> Result<String, Error> {
+            if id >= self.seats() {
+                return Err(Error::OutOfRange {
+                    what: "id",
+                    value: id,
+                    max: self.seats() - 1,
+                });
+            }
+            self.encode(id >> self.col_bits, id & (self.cols() - 1))
+        }
+    }
+
+    /// `7:FB,3:LR` is the puzzle's layout: 7 bits of `F`/`B` for rows, then 3 of `L`/`R`.
+    impl FromStr for Codec {
+        type Err = Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let half = |s: &str| -> Option<(u32, Alphabet)> {
+                let (bits, letters) = s.split_once(':')?;
+                let mut letters = letters.chars();
+                let alphabet = Alphabet::new(letters.next()?, letters.next()?);
+                match letters.next() {
+                    Some(_) => None,
+                    None => Some((bits.parse().ok()?, alphabet)),
+                }
+            };
+            let (rows, cols) = s.split_once(',').ok_or(Error::BadLayout)?;
+            let ((row_bits, rows), (col_bits, cols)) =
+                half(rows).zip(half(cols)).ok_or(Error::BadLayout)?;
+            Self::new(row_bits, col_bits, rows, cols)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_aoc_layout() {
+            let codec = Codec::AOC;
+            assert_eq!(
+                codec.decode("BFFFBBFRRR"),
+                Ok(Decoded {
+                    row: 70,
+                    col: 7,
+                    id: 567
+                })
+            );
+            assert_eq!(codec.decode("BBFFBBFRLL").map(|d| d.id), Ok(820));
+            assert_eq!(codec.encode(70, 7).as_deref(), Ok("BFFFBBFRRR"));
+            assert_eq!(codec.encode_id(820).as_deref(), Ok("BBFFBBFRLL"));
+            assert_eq!("7:FB,3:LR".parse(), Ok(codec));
+            assert!("40:FB,20:LR".parse::<Codec>().is_ok());
+
+            // every pass in the input comes back the same
+            for pass in include_str!("input.txt").lines() {
+                let decoded = codec.decode(pass).unwrap();
+                assert_eq!(codec.encode(decoded.row, decoded.col).unwrap(), pass);
+                assert_eq!(codec.encode_id(decoded.id).unwrap(), pass);
+            }
+
+            // and agrees with the fixed-layout `Seat` on every seat
+            for id in 0..1024 {
+                let seat = crate::Seat::from_id(id).unwrap();
+                let decoded = codec.decode(&seat.to_string()).unwrap();
+                assert_eq!(decoded.id, u64::from(seat.id()));
+                assert_eq!(
+                    (decoded.row, decoded.col),
+                    (seat.row().into(), seat.col().into())
+                );
+            }
+        }
+
+        #[test]
+        fn test_other_layouts() {
+            let codec: Codec = "5:ab,4:xy".parse().unwrap();
+            assert_eq!(codec.pass_len(), 9);
+            assert_eq!(codec.seats(), 512);
+            assert_eq!(
+                codec.decode("bbaabyxxy"),
+                Ok(Decoded {
+                    row: 25,
+                    col: 9,
+                    id: 25 * 16 + 9
+                })
+            );
+            for id in 0..codec.seats() {
+                let pass = codec.encode_id(id).unwrap();
+                assert_eq!(codec.decode(&pass).unwrap().id, id);
+            }
+
+            // the same letters for rows and columns is fine, the position says which is which
+            let binary: Codec = "2:01,2:01".parse().unwrap();
+            assert_eq!(binary.encode(2, 1).as_deref(), Ok("1001"));
+        }
+
+        #[test]
+        fn test_errors() {
+            let codec = Codec::AOC;
+            assert_eq!(
+                codec.decode("FBFBBFFRL"),
+                Err(Error::WrongLength {
+                    expected: 10,
+                    found: 9
+                })
+            );
+            assert_eq!(
+                codec.decode("FBFBBFFRLX"),
+                Err(Error::InvalidChar {
+                    position: 9,
+                    found: 'X'
+                })
+            );
+            // column letters in the row half don't count
+            assert_eq!(
+                codec.decode("FBFBBFLRLR"),
+                Err(Error::InvalidChar {
+                    position: 6,
+                    found: 'L'
+                })
+            );
+            assert_eq!(
+                codec.encode(128, 0),
+                Err(Error::OutOfRange {
+                    what: "row",
+                    value: 128,
+                    max: 127
+                })
+            );
+            assert!(codec.encode(0, 8).is_err());
+            assert!(codec.encode_id(1024).is_err());
+
+            for bad in [
+                "7:FB",
+                "7:FF,3:LR",
+                "0:FB,3:LR",
+                "7:FBX,3:LR",
+                "40:FB,30:LR",
+                "4294967295:FB,1:LR",
+                "x:FB,3:LR",
+            ] {
+                assert_eq!(bad.parse::<Codec>(), Err(Error::BadLayout), "{}", bad);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL_INPUT: &str = include_str!("input.txt");
+
+    /// Run `$test` once per decoder, as `D`.
+    macro_rules! each_decoder {
+        ($test: ident) => {
+            $test::<Bits>();
+            $test::<ShiftMask>();
+            $test::<Batch>();
+        };
+    }
+
+    #[test]
+    fn test_examples() {
+        fn test<D: Decoder>() {
+            for (pass, row, col, id) in [
+                ("FBFBBFFRLR", 44, 5, 357),
+                ("BFFFBBFRRR", 70, 7, 567),
+                ("FFFBBBFRRR", 14, 7, 119),
+                ("BBFFBBFRLL", 102, 4, 820),
+            ] {
+                let seat = Seat::parse_with::<D>(pass).unwrap();
+                assert_eq!(
+                    (seat.row(), seat.col(), seat.id()),
+                    (row, col, id),
+                    "{}",
+                    D::NAME
+                );
+                assert_eq!(Seat::new(row, col), Some(seat));
+                assert_eq!(seat.to_string(), pass);
+            }
+        }
+        each_decoder!(test);
+        assert_eq!(Seat::new(128, 0), None);
+        assert_eq!(Seat::from_id(1024), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        fn test<D: Decoder>() {
+            let bad = |position, found| Err(Error::InvalidChar { position, found });
+            let length = |found| {
+                Err(Error::WrongLength {
+                    expected: Seat::LEN,
+                    found,
+                })
+            };
+            assert_eq!(D::decode("FBFBBFFRL"), length(9));
+            assert_eq!(D::decode("FBFBBFFRLRR"), length(11));
+            assert_eq!(D::decode("FBFBBFFRLR\r"), length(11));
+            assert_eq!(D::decode("FBFBBFFFLR"), bad(7, 'F'));
+            assert_eq!(D::decode("FBFBBFLRLR"), bad(6, 'L'));
+            assert_eq!(D::decode("FBFBBFFRLé"), bad(9, 'é'));
+            // the length comes first, the same as any other layout
+            assert_eq!(D::decode("FBF"), length(3));
+            assert_eq!(D::decode("FBX"), length(3));
+
+            assert_eq!(
+                D::decode_all("FBFBBFFRLR\nFBFBBFFRLX\n"),
+                Err(LineError {
+                    line: 2,
+                    error: Error::InvalidChar {
+                        position: 9,
+                        found: 'X'
+                    }
+                })
+            );
+        }
+        each_decoder!(test);
+    }
+
+    #[test]
+    fn test_decoders_agree() {
+        // every seat, and the puzzle input in its various line endings
+        let every: String = (0..1024).map(|id| format!("{}\n", Seat(id))).collect();
+        let inputs = [
+            every.clone(),
+            every.trim_end().to_owned(),
+            every.replace('\n', "\r\n"),
+            FULL_INPUT.to_owned(),
+            String::new(),
+            "\n".to_owned(),
+            "FBFBBFFRLR\n\nFBFBBFFRLR".to_owned(),
+        ];
+        for input in &inputs {
+            let expected = Bits::decode_all(input);
+            assert_eq!(ShiftMask::decode_all(input), expected, "{:?}", input);
+            assert_eq!(Batch::decode_all(input), expected, "{:?}", input);
+        }
+        let seats = Batch::decode_all(&every).unwrap();
+        assert_eq!(seats, (0..1024).map(Seat).collect::<Vec<_>>());
+    }
+}
@@ -1,67 +1,15 @@
 #![allow(dead_code)]
 
-mod part_one {
-    use bitvec::prelude::*;
-
-    #[derive(Default, Debug, PartialEq)]
-    struct Seat {
-        row: u8,
-        col: u8,
-    }
-
-    impl Seat {
-        const ROW_BITS: usize = 7;
-        const COL_BITS: usize = 3;
-
-        fn parse(input: &str) -> Self {
-            let bytes = input.as_bytes();
-            let mut res: Seat = Default::default();
-
-            {
-                // operate on res.row 👇
-                let row = BitSlice::<Msb0, _>::from_element_mut(&mut res.row);
-                // for each F or B elem...
-                for (i, &b) in bytes[0..Self::ROW_BITS].iter().enumerate() {
-                    // set the corresponding bit, positions 0 - 6
-                    row.set(
-                        (8 - Self::ROW_BITS) + i,
-                        match b {
-                            b'F' => false,
-                            b'B' => true,
-                            _ => panic!("unexpected row letter: {}", b as char),
-                        },
-                    );
-                }
-            }
-            {
-                // operate on res.col 👇
-                let col = BitSlice::<Msb0, _>::from_element_mut(&mut res.col);
-                for (i, &b) in bytes[Self::ROW_BITS..][..Self::COL_BITS].iter().enumerate() {
-                    col.set(
-                        (8 - Self::COL_BITS) + i,
-                        match b {
-                            b'L' => false,
-                            b'R' => true,
-                            _ => panic!("unexpected col letter: {}", b as char),
-                        },
-                    );
-                }
-            }
-            res
-        }
-
-        fn id(&self) -> u64 {
-            // or do a confusing bit shift like so:
-            // ((self.row as u64) << Self::COL_BITS) + (self.col as u64)
-            // shifting 3 bits is the same result as multiplying by 8
+use day_05_binary_boarding::codec;
 
-            // but here's the simple solution:
-            ((self.row as u64) * 8) + (self.col as u64)
-        }
-    }
+mod part_one {
+    use day_05_binary_boarding::Seat;
 
     pub fn calc(s: &str) -> Option<u64> {
-        s.lines().map(Seat::parse).map(|seat| seat.id()).max()
+        s.lines()
+            .map(|pass| Seat::parse(pass).expect("puzzle input has valid passes"))
+            .map(|seat| u64::from(seat.id()))
+            .max()
     }
 
     #[cfg(test)]
@@ -79,13 +27,13 @@ mod part_one {
             // FBFBBFF == 0101100 == 44
             // RLR == 101 == 5
             let input = "FBFBBFFRLR";
-            let seat = Seat::parse(input);
-            assert_eq!(seat, Seat { row: 44, col: 5 });
+            let seat = Seat::parse(input).unwrap();
+            assert_eq!((seat.row(), seat.col()), (44, 5));
         }
 
         #[test]
         fn test_id() {
-            let seat = Seat { row: 102, col: 4 };
+            let seat = Seat::new(102, 4).unwrap();
             assert_eq!(seat.id(), 820);
         }
 
@@ -93,14 +41,8 @@ mod part_one {
         fn test_id_macro() {
             macro_rules! validate {
                 ($input: expr, $row: expr, $col: expr, $id: expr) => {
-                    let seat = Seat::parse($input);
-                    assert_eq!(
-                        seat,
-                        Seat {
-                            row: $row,
-                            col: $col
-                        }
-                    );
+                    let seat = Seat::parse($input).unwrap();
+                    assert_eq!((seat.row(), seat.col()), ($row, $col));
                     assert_eq!(seat.id(), $id);
                 };
             }
@@ -131,47 +73,24 @@ mod part_two {
         44 * 8 + 5 == FBFBBFFRLR !
         just convert them to binary *in place*!
         pretty wily!
-    */
 
-    use bitvec::prelude::*;
-
-    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
-    struct Seat(u16);
+        which is all `Seat` stores.
+    */
 
-    impl Seat {
-        fn parse(input: &str) -> Self {
-            let mut res: Self = Default::default();
-
-            // note the reversed Bit order... least to greatest
-            let bits = BitSlice::<Lsb0, _>::from_element_mut(&mut res.0);
-            // then iterate backwards so the indices are nice and neat!
-            for (i, &b) in input.as_bytes().iter().rev().enumerate() {
-                // rlrfbbfbf (reverse order!)
-                bits.set(
-                    i,
-                    match b {
-                        b'F' | b'L' => false, // low bits
-                        b'B' | b'R' => true,  // high bits
-                        _ => panic!("unexpected letter: {}", b as char),
-                    },
-                )
-            }
-            res
-        }
-    }
+    use day_05_binary_boarding::{Batch, Decoder, Seat};
 
     fn calc(s: &str) -> Option<Seat> {
-        let mut ids: Vec<_> = s.lines().map(Seat::parse).collect();
+        let mut ids = Batch::decode_all(s).expect("puzzle input has valid passes");
         ids.sort();
 
         let mut last_id: Option<Seat> = None;
         for id in ids {
             if let Some(last_id) = last_id {
                 // calculate the gap between the last seat and current seat
-                let gap = id.0 - last_id.0;
+                let gap = id.id() - last_id.id();
                 // if it's greater than one, there's a hole for us to sit in :)
                 if gap > 1 {
-                    return Some(Seat(last_id.0 + 1));
+                    return Seat::from_id(last_id.id() + 1);
                 }
             }
             last_id = Some(id);
@@ -185,26 +104,907 @@ mod part_two {
 
         #[test]
         fn part_two_full() {
-            assert_eq!(calc(super::super::FULL_INPUT), Some(Seat(524)))
+            assert_eq!(calc(super::super::FULL_INPUT), Seat::from_id(524))
         }
 
         #[test]
         fn test_parse() {
             let input = "FBFBBFFRLR";
-            assert_eq!(Seat::parse(input), Seat(357));
+            assert_eq!(Seat::parse(input).unwrap().id(), 357);
         }
 
         #[test]
         fn test_seat_id() {
-            assert_eq!(Seat::parse("BFFFBBFRRR"), Seat(567));
-            assert_eq!(Seat::parse("FFFBBBFRRR"), Seat(119));
-            assert_eq!(Seat::parse("BBFFBBFRLL"), Seat(820));
+            assert_eq!(Seat::parse("BFFFBBFRRR").unwrap().id(), 567);
+            assert_eq!(Seat::parse("FFFBBBFRRR").unwrap().id(), 119);
+            assert_eq!(Seat::parse("BBFFBBFRLL").unwrap().id(), 820);
+        }
+    }
+}
+
+/// Reconciling a whole manifest of passes against a plane.
+mod manifest {
+    use super::codec::{self, Codec};
+    use std::{collections::BTreeMap, ops::Range, ops::RangeInclusive};
+
+    /// A plane's layout. Its passes can describe more rows and columns than it has, but not
+    /// fewer, and it has at most [`Plane::MAX_SEATS`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Plane {
+        pub codec: Codec,
+        rows: u64,
+        cols: u64,
+    }
+
+    impl Plane {
+        /// 16 million seats is already far more than any plane, and the manifest and simulation
+        /// keep a slot for every one of them.
+        pub const MAX_SEATS: u64 = 1 << 24;
+
+        /// `rows` of `cols` seats, or `None` if `codec`'s passes can't name them all or there
+        /// are more than [`Plane::MAX_SEATS`].
+        pub fn new(codec: Codec, rows: u64, cols: u64) -> Option<Self> {
+            let named = rows <= codec.rows() && cols <= codec.cols();
+            let seats = rows
+                .checked_mul(cols)
+                .is_some_and(|seats| seats <= Self::MAX_SEATS);
+            (named && seats).then_some(Self { codec, rows, cols })
+        }
+
+        /// Every seat a pass can name exists, if that's no more than [`Plane::MAX_SEATS`].
+        pub fn full(codec: Codec) -> Option<Self> {
+            Self::new(codec, codec.rows(), codec.cols())
+        }
+
+        pub fn rows(&self) -> u64 {
+            self.rows
+        }
+
+        pub fn cols(&self) -> u64 {
+            self.cols
+        }
+
+        pub fn seats(&self) -> u64 {
+            self.rows * self.cols
+        }
+
+        /// Seats in front-to-back, left-to-right order, as used to index `Analysis::occupancy`
+        fn index(&self, row: u64, col: u64) -> usize {
+            (row * self.cols + col) as usize
+        }
+
+        fn id(&self, index: usize) -> u64 {
+            let index = index as u64;
+            self.codec.id(index / self.cols, index % self.cols)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Reason {
+        Invalid(codec::Error),
+        /// A seat the plane doesn't have
+        OutOfRange {
+            row: u64,
+            col: u64,
+        },
+    }
+
+    /// A pass that doesn't name a seat on the plane.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Rejected {
+        /// Line number, from 1
+        pub line: usize,
+        pub pass: String,
+        pub reason: Reason,
+    }
+
+    /// A seat more than one pass names.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Duplicate {
+        pub id: u64,
+        pub lines: Vec<usize>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Analysis {
+        pub plane: Plane,
+        /// How many passes name each seat, in `Plane::index` order
+        pub occupancy: Vec<u32>,
+        /// Free seats with someone on either side, which is where the puzzle's own seat is
+        pub candidates: Vec<u64>,
+        /// Every stretch of free seats, by id
+        pub empty_runs: Vec<RangeInclusive<u64>>,
+        pub duplicates: Vec<Duplicate>,
+        pub rejected: Vec<Rejected>,
+        /// Rows with nobody in them before the first occupied row and after the last
+        pub empty_front_rows: Range<u64>,
+        pub empty_back_rows: Range<u64>,
+    }
+
+    /// Analyse one pass per line.
+    pub fn analyse(plane: Plane, passes: &str) -> Analysis {
+        let mut occupancy = vec![0; plane.seats() as usize];
+        let mut lines_by_seat: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        let mut rejected = vec![];
+
+        for (i, pass) in passes.lines().enumerate() {
+            let line = i + 1;
+            let reject = |reason| Rejected {
+                line,
+                pass: pass.to_owned(),
+                reason,
+            };
+            match plane.codec.decode(pass) {
+                Err(e) => rejected.push(reject(Reason::Invalid(e))),
+                Ok(seat) if seat.row >= plane.rows || seat.col >= plane.cols => {
+                    rejected.push(reject(Reason::OutOfRange {
+                        row: seat.row,
+                        col: seat.col,
+                    }))
+                }
+                Ok(seat) => {
+                    let index = plane.index(seat.row, seat.col);
+                    occupancy[index] += 1;
+                    lines_by_seat.entry(index).or_default().push(line);
+                }
+            }
+        }
+
+        let duplicates = lines_by_seat
+            .into_iter()
+            .filter(|(_, lines)| lines.len() > 1)
+            .map(|(index, lines)| Duplicate {
+                id: plane.id(index),
+                lines,
+            })
+            .collect();
+
+        let free = |index: usize| occupancy[index] == 0;
+        let candidates = (1..occupancy.len().saturating_sub(1))
+            .filter(|&i| free(i) && !free(i - 1) && !free(i + 1))
+            .map(|i| plane.id(i))
+            .collect();
+
+        let mut empty_runs = vec![];
+        let mut run_start = None;
+        for i in 0..=occupancy.len() {
+            match (run_start, i < occupancy.len() && free(i)) {
+                (None, true) => run_start = Some(i),
+                (Some(start), false) => {
+                    empty_runs.push(plane.id(start)..=plane.id(i - 1));
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        let row_occupied = |row: u64| (0..plane.cols).any(|col| !free(plane.index(row, col)));
+        let first = (0..plane.rows).find(|&row| row_occupied(row));
+        let last = (0..plane.rows).rev().find(|&row| row_occupied(row));
+        let (empty_front_rows, empty_back_rows) = match (first, last) {
+            (Some(first), Some(last)) => (0..first, last + 1..plane.rows),
+            _ => (0..plane.rows, plane.rows..plane.rows),
+        };
+
+        Analysis {
+            plane,
+            occupancy,
+            candidates,
+            empty_runs,
+            duplicates,
+            rejected,
+            empty_front_rows,
+            empty_back_rows,
+        }
+    }
+
+    /// The seat map, a row per line: `#` taken, `.` free, `O` free between two taken seats and
+    /// `D` taken more than once.
+    impl std::fmt::Display for Analysis {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let plane = &self.plane;
+            let width = (plane.rows.max(1) - 1).to_string().len();
+            for row in 0..plane.rows {
+                write!(f, "{:>width$} ", row, width = width)?;
+                for col in 0..plane.cols {
+                    let index = plane.index(row, col);
+                    let c = match self.occupancy[index] {
+                        0 if self.candidates.contains(&plane.id(index)) => 'O',
+                        0 => '.',
+                        1 => '#',
+                        _ => 'D',
+                    };
+                    write!(f, "{}", c)?;
+                }
+                writeln!(f)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_full_input() {
+            let analysis = analyse(Plane::full(Codec::AOC).unwrap(), super::super::FULL_INPUT);
+            // the puzzle's answer is the only one
+            assert_eq!(analysis.candidates, [524]);
+            assert!(analysis.duplicates.is_empty());
+            assert!(analysis.rejected.is_empty());
+
+            let runs = &analysis.empty_runs;
+            assert_eq!(runs.len(), 3);
+            assert_eq!(runs[0].start(), &0);
+            assert_eq!(runs[1], 524..=524);
+            assert_eq!(runs[2].end(), &1023);
+            assert_eq!(analysis.empty_front_rows.start, 0);
+            assert_eq!(analysis.empty_back_rows.end, 128);
+
+            let map = analysis.to_string();
+            assert_eq!(map.lines().count(), 128);
+            assert_eq!(map.matches('O').count(), 1);
+            assert_eq!(
+                map.matches('#').count(),
+                super::super::FULL_INPUT.lines().count()
+            );
+        }
+
+        #[test]
+        fn test_small_manifest() {
+            let codec: Codec = "2:FB,2:LR".parse().unwrap();
+            // 3 rows of 3 seats on a plane whose passes could name 4 of each
+            let plane = Plane::new(codec, 3, 3).unwrap();
+            assert_eq!(Plane::new(codec, 5, 3), None);
+            let wide: Codec = "40:FB,20:LR".parse().unwrap();
+            assert_eq!(Plane::full(wide), None);
+            assert!(Plane::new(wide, 1 << 20, 16).is_some());
+            let passes = "FBLL\nFBLR\nFBRL\nBFLL\nBFRL\nBFRL\nBBLL\nFBRR\nFBXL\nFBL";
+            let analysis = analyse(plane, passes);
+
+            assert_eq!(analysis.occupancy, [0, 0, 0, 1, 1, 1, 1, 0, 2]);
+            // id 9 (row 2, column 1) is between two taken seats in order
+            assert_eq!(analysis.candidates, [9]);
+            assert_eq!(analysis.empty_runs, [0..=2, 9..=9]);
+            assert_eq!(
+                analysis.duplicates,
+                [Duplicate {
+                    id: 10,
+                    lines: vec![5, 6]
+                }]
+            );
+            let rejected: Vec<_> = analysis
+                .rejected
+                .iter()
+                .map(|r| (r.line, r.reason.clone()))
+                .collect();
+            assert_eq!(
+                rejected,
+                [
+                    (7, Reason::OutOfRange { row: 3, col: 0 }),
+                    (8, Reason::OutOfRange { row: 1, col: 3 }),
+                    (
+                        9,
+                        Reason::Invalid(codec::Error::InvalidChar {
+                            position: 2,
+                            found: 'X'
+                        })
+                    ),
+                    (
+                        10,
+                        Reason::Invalid(codec::Error::WrongLength {
+                            expected: 4,
+                            found: 3
+                        })
+                    ),
+                ]
+            );
+            assert_eq!(analysis.empty_front_rows, 0..1);
+            assert_eq!(analysis.empty_back_rows, 3..3);
+            assert_eq!(analysis.to_string(), "0 ...\n1 ###\n2 #OD\n");
+
+            let empty = analyse(plane, "");
+            assert_eq!(empty.empty_front_rows, 0..3);
+            assert_eq!(empty.empty_runs, [0..=10]);
+            assert!(empty.candidates.is_empty());
+        }
+    }
+}
+
+/// Boarding a plane under different seat-allocation strategies, to generate manifests.
+mod simulation {
+    use super::manifest::Plane;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, RngCore};
+
+    /// Consecutive passengers with the same group travel together.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Passenger {
+        pub group: u64,
+    }
+
+    /// `count` passengers in groups of 1 to `max_group`.
+    pub fn passengers(count: usize, max_group: usize, rng: &mut dyn RngCore) -> Vec<Passenger> {
+        let mut passengers = Vec::with_capacity(count);
+        let mut group = 0;
+        while passengers.len() < count {
+            let size = rng
+                .gen_range(1..=max_group.max(1))
+                .min(count - passengers.len());
+            passengers.extend(std::iter::repeat_n(Passenger { group }, size));
+            group += 1;
+        }
+        passengers
+    }
+
+    /// The plane as boarding goes on, and the order one strategy hands its seats out in.
+    #[derive(Debug, Clone)]
+    pub struct Cabin {
+        plane: Plane,
+        taken: Vec<bool>,
+        /// Every seat by index, in the strategy's order
+        order: Vec<u32>,
+        /// Everything in `order` before this is taken
+        next: usize,
+        /// The longest run of free seats in each row
+        longest: Vec<u64>,
+        /// Rows by their longest free run, so `rows_by_run[n]` holds every row with `longest` n
+        rows_by_run: Vec<Vec<u64>>,
+        /// Where each row is in its `rows_by_run` list
+        slot: Vec<usize>,
+    }
+
+    impl Cabin {
+        /// An empty cabin, with its seats ranked by `strategy`. Ties go in a random order, so
+        /// taking seats off the front picks at random between equals.
+        pub fn new(plane: Plane, strategy: &dyn Strategy, rng: &mut dyn RngCore) -> Self {
+            // `Plane` keeps this within `Plane::MAX_SEATS`
+            let mut order: Vec<u32> = (0..plane.seats() as u32).collect();
+            order.shuffle(rng);
+            order.sort_by_cached_key(|&seat| {
+                let (row, col) = (seat as u64 / plane.cols(), seat as u64 % plane.cols());
+                strategy.rank(plane, row, col)
+            });
+            let mut rows_by_run = vec![vec![]; plane.cols() as usize + 1];
+            rows_by_run[plane.cols() as usize] = (0..plane.rows()).collect();
+            Self {
+                plane,
+                taken: vec![false; plane.seats() as usize],
+                order,
+                next: 0,
+                longest: vec![plane.cols(); plane.rows() as usize],
+                rows_by_run,
+                slot: (0..plane.rows() as usize).collect(),
+            }
+        }
+
+        pub fn plane(&self) -> Plane {
+            self.plane
+        }
+
+        pub fn is_free(&self, row: u64, col: u64) -> bool {
+            !self.taken[(row * self.plane.cols() + col) as usize]
+        }
+
+        /// A random row with at least `size` free seats side by side.
+        pub fn row_with_room(&self, size: u64, rng: &mut dyn RngCore) -> Option<u64> {
+            let rows = self.rows_by_run.get(size as usize..)?;
+            let total: usize = rows.iter().map(Vec::len).sum();
+            if total == 0 {
+                return None;
+            }
+            let mut pick = rng.gen_range(0..total);
+            for rows in rows {
+                match rows.get(pick) {
+                    Some(&row) => return Some(row),
+                    None => pick -= rows.len(),
+                }
+            }
+            None
+        }
+
+        /// The first `size` free seats in the strategy's order, as `(row, col)`.
+        pub fn lowest(&self, size: usize) -> Vec<(u64, u64)> {
+            let cols = self.plane.cols();
+            self.order[self.next..]
+                .iter()
+                .filter(|&&seat| !self.taken[seat as usize])
+                .take(size)
+                .map(|&seat| (seat as u64 / cols, seat as u64 % cols))
+                .collect()
+        }
+
+        fn take(&mut self, row: u64, col: u64) {
+            debug_assert!(
+                self.is_free(row, col),
+                "row {} seat {} given twice",
+                row,
+                col
+            );
+            self.taken[(row * self.plane.cols() + col) as usize] = true;
+            while let Some(&seat) = self.order.get(self.next) {
+                match self.taken[seat as usize] {
+                    true => self.next += 1,
+                    false => break,
+                }
+            }
+
+            // move the row to the list for its new longest run
+            let (mut run, mut longest) = (0, 0);
+            for col in 0..self.plane.cols() {
+                run = match self.is_free(row, col) {
+                    true => run + 1,
+                    false => 0,
+                };
+                longest = longest.max(run);
+            }
+            let old = self.longest[row as usize];
+            if longest != old {
+                let rows = &mut self.rows_by_run[old as usize];
+                let slot = self.slot[row as usize];
+                rows.swap_remove(slot);
+                if let Some(&moved) = rows.get(slot) {
+                    self.slot[moved as usize] = slot;
+                }
+                let rows = &mut self.rows_by_run[longest as usize];
+                self.slot[row as usize] = rows.len();
+                rows.push(row);
+                self.longest[row as usize] = longest;
+            }
+        }
+    }
+
+    /// How a group gets its seats.
+    pub trait Strategy {
+        /// The name used to pick this strategy at runtime, see [`by_name`].
+        fn name(&self) -> &'static str;
+
+        /// Where a seat comes in this strategy's order, lowest first. Seats that tie are
+        /// handed out at random; by default they all do.
+        fn rank(&self, _plane: Plane, _row: u64, _col: u64) -> u64 {
+            0
+        }
+
+        /// `size` distinct free seats as `(row, col)`, or `None` to turn the group away. Only
+        /// called when there are enough free seats. By default, the best ranked ones.
+        fn allocate(
+            &self,
+            cabin: &Cabin,
+            size: usize,
+            _rng: &mut dyn RngCore,
+        ) -> Option<Vec<(u64, u64)>> {
+            Some(cabin.lowest(size))
+        }
+    }
+
+    /// Anywhere.
+    pub struct Random;
+
+    impl Strategy for Random {
+        fn name(&self) -> &'static str {
+            "random"
+        }
+    }
+
+    /// Window seats, then the ones next to them, working in towards the middle.
+    pub struct WindowFirst;
+
+    impl Strategy for WindowFirst {
+        fn name(&self) -> &'static str {
+            "window-first"
+        }
+
+        fn rank(&self, plane: Plane, _row: u64, col: u64) -> u64 {
+            col.min(plane.cols() - 1 - col)
+        }
+    }
+
+    /// The back row fills first.
+    pub struct BackToFront;
+
+    impl Strategy for BackToFront {
+        fn name(&self) -> &'static str {
+            "back-to-front"
+        }
+
+        fn rank(&self, plane: Plane, row: u64, _col: u64) -> u64 {
+            plane.rows() - 1 - row
+        }
+    }
+
+    /// Side by side in one row where possible, or wherever is left when not.
+    pub struct GroupsTogether;
+
+    impl Strategy for GroupsTogether {
+        fn name(&self) -> &'static str {
+            "groups-together"
+        }
+
+        fn allocate(
+            &self,
+            cabin: &Cabin,
+            size: usize,
+            rng: &mut dyn RngCore,
+        ) -> Option<Vec<(u64, u64)>> {
+            // a random row with room, then a random place in it
+            let size = size as u64;
+            let row = match cabin.row_with_room(size, rng) {
+                Some(row) => row,
+                None => return Some(cabin.lowest(size as usize)),
+            };
+            let starts: Vec<u64> = (0..=cabin.plane().cols() - size)
+                .filter(|&col| (col..col + size).all(|c| cabin.is_free(row, c)))
+                .collect();
+            let &col = starts.choose(rng)?;
+            Some((col..col + size).map(|c| (row, c)).collect())
+        }
+    }
+
+    pub fn builtin() -> Vec<Box<dyn Strategy>> {
+        vec![
+            Box::new(Random),
+            Box::new(WindowFirst),
+            Box::new(BackToFront),
+            Box::new(GroupsTogether),
+        ]
+    }
+
+    pub fn by_name(name: &str) -> Option<Box<dyn Strategy>> {
+        builtin()
+            .into_iter()
+            .find(|strategy| strategy.name() == name)
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub struct Stats {
+        pub seats: u64,
+        pub passengers: u64,
+        pub seated: u64,
+        /// Passengers whose group didn't fit, or that the strategy refused
+        pub turned_away: u64,
+        pub groups: u64,
+        /// Seated groups of more than one sitting side by side in a single row
+        pub groups_together: u64,
+        pub groups_split: u64,
+        /// Rows with anyone in them
+        pub rows_used: u64,
+    }
+
+    impl Stats {
+        /// The share of seats taken, from 0 to 1.
+        pub fn utilisation(&self) -> f64 {
+            match self.seats {
+                0 => 0.0,
+                seats => self.seated as f64 / seats as f64,
+            }
+        }
+    }
+
+    impl std::fmt::Display for Stats {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            writeln!(
+                f,
+                "{} of {} seats taken ({:.1}%), {} rows used",
+                self.seated,
+                self.seats,
+                self.utilisation() * 100.0,
+                self.rows_used
+            )?;
+            writeln!(
+                f,
+                "{} passengers in {} groups, {} turned away",
+                self.passengers, self.groups, self.turned_away
+            )?;
+            write!(
+                f,
+                "{} groups seated together, {} split",
+                self.groups_together, self.groups_split
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Boarding {
+        /// In boarding order
+        pub passes: Vec<String>,
+        pub stats: Stats,
+    }
+
+    /// Seat `passengers` one group at a time.
+    pub fn simulate(
+        plane: Plane,
+        passengers: impl IntoIterator<Item = Passenger>,
+        strategy: &dyn Strategy,
+        rng: &mut dyn RngCore,
+    ) -> Boarding {
+        let mut cabin = Cabin::new(plane, strategy, rng);
+        let mut free = plane.seats();
+        let mut passes = vec![];
+        let mut stats = Stats {
+            seats: free,
+            ..Stats::default()
+        };
+
+        let mut passengers = passengers.into_iter().peekable();
+        while let Some(first) = passengers.next() {
+            let mut size = 1;
+            while passengers.next_if_eq(&first).is_some() {
+                size += 1;
+            }
+            stats.passengers += size;
+            stats.groups += 1;
+
+            let seats = match size <= free {
+                true => strategy.allocate(&cabin, size as usize, rng),
+                false => None,
+            };
+            let seats = match seats {
+                Some(seats) => seats,
+                None => {
+                    stats.turned_away += size;
+                    continue;
+                }
+            };
+            assert_eq!(
+                seats.len() as u64,
+                size,
+                "{} seated the wrong number",
+                strategy.name()
+            );
+
+            if size > 1 {
+                let (row, col) = seats.iter().min().copied().unwrap_or_default();
+                match seats.iter().all(|&(r, c)| r == row && c - col < size) {
+                    true => stats.groups_together += 1,
+                    false => stats.groups_split += 1,
+                }
+            }
+            for (row, col) in seats {
+                cabin.take(row, col);
+                let pass = plane.codec.encode(row, col);
+                passes.push(pass.expect("every seat on the plane has a pass"));
+            }
+            stats.seated += size;
+            free -= size;
+        }
+
+        stats.rows_used = (0..plane.rows())
+            .filter(|&row| (0..plane.cols()).any(|col| !cabin.is_free(row, col)))
+            .count() as u64;
+        Boarding { passes, stats }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::{codec::Codec, manifest};
+        use super::*;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        fn board(strategy: &str, count: usize, max_group: usize, seed: u64) -> Boarding {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let passengers = passengers(count, max_group, &mut rng);
+            let strategy = by_name(strategy).unwrap();
+            simulate(
+                Plane::full(Codec::AOC).unwrap(),
+                passengers,
+                &*strategy,
+                &mut rng,
+            )
+        }
+
+        #[test]
+        fn test_manifests_check_out() {
+            for strategy in builtin() {
+                let boarding = board(strategy.name(), 900, 4, 7);
+                let stats = boarding.stats;
+                assert_eq!(stats.passengers, 900);
+                assert_eq!(stats.seated + stats.turned_away, 900);
+                assert_eq!(boarding.passes.len() as u64, stats.seated);
+                assert_eq!(stats.seats, 1024);
+
+                let analysis = manifest::analyse(
+                    Plane::full(Codec::AOC).unwrap(),
+                    &boarding.passes.join("\n"),
+                );
+                assert!(analysis.duplicates.is_empty(), "{}", strategy.name());
+                assert!(analysis.rejected.is_empty(), "{}", strategy.name());
+
+                // the same seed gives the same manifest
+                assert_eq!(board(strategy.name(), 900, 4, 7), boarding);
+            }
+            assert_ne!(board("random", 900, 4, 7), board("random", 900, 4, 8));
+        }
+
+        #[test]
+        fn test_strategies() {
+            let decode = |pass: &String| Codec::AOC.decode(pass).unwrap();
+
+            let boarding = board("window-first", 256, 3, 1);
+            assert!(boarding
+                .passes
+                .iter()
+                .all(|pass| [0, 7].contains(&decode(pass).col)));
+
+            let boarding = board("back-to-front", 80, 3, 1);
+            assert!(boarding.passes.iter().all(|pass| decode(pass).row >= 118));
+            assert_eq!(boarding.stats.rows_used, 10);
+
+            let boarding = board("groups-together", 600, 4, 1);
+            assert_eq!(boarding.stats.groups_split, 0);
+            assert!(boarding.stats.groups_together > 0);
+
+            // a full plane turns everyone else away
+            let boarding = board("groups-together", 2000, 4, 1);
+            assert!(boarding.stats.seated <= 1024);
+            assert!(boarding.stats.seated > 1000);
+            assert_eq!(boarding.stats.seated + boarding.stats.turned_away, 2000);
+            assert!(boarding.stats.utilisation() > 0.97);
+        }
+
+        #[test]
+        fn test_row_with_room() {
+            let mut rng = StdRng::seed_from_u64(1);
+            let plane = manifest::Plane::new(Codec::AOC, 2, 8).unwrap();
+            let mut cabin = Cabin::new(plane, &WindowFirst, &mut rng);
+            for col in [2, 5] {
+                cabin.take(0, col);
+            }
+            assert!(cabin.row_with_room(3, &mut rng).is_some());
+            assert_eq!(cabin.row_with_room(4, &mut rng), Some(1));
+            cabin.take(1, 3);
+            assert_eq!(cabin.row_with_room(5, &mut rng), None);
+            assert!((0..20).all(|_| cabin.row_with_room(4, &mut rng) == Some(1)));
+            assert_eq!(cabin.row_with_room(9, &mut rng), None);
         }
     }
 }
 
 fn main() {
-    println!("Hello, world! (see tests for actual solutions)");
+    // `decode BFFFBBFRRR`, `encode 567` or `encode 70 7`, with `--layout 7:FB,3:LR` for
+    // other planes
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let codec = match args.iter().position(|arg| arg == "--layout") {
+        Some(i) => match args.get(i + 1).map(|layout| layout.parse()) {
+            Some(Ok(codec)) => codec,
+            Some(Err(e)) => {
+                eprintln!("bad --layout: {}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("--layout needs a value like 7:FB,3:LR");
+                std::process::exit(1);
+            }
+        },
+        None => codec::Codec::AOC,
+    };
+    // the manifest and simulation need the whole plane in memory
+    let full_plane = || match manifest::Plane::full(codec) {
+        Some(plane) => plane,
+        None => {
+            eprintln!(
+                "--layout has {} seats, more than the {} a manifest or simulation can hold",
+                codec.seats(),
+                manifest::Plane::MAX_SEATS
+            );
+            std::process::exit(1);
+        }
+    };
+    let operands: Vec<&String> = args
+        .iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--layout")
+        .collect();
+
+    match args.first().map(String::as_str) {
+        Some("decode") => {
+            let mut failed = false;
+            for pass in operands {
+                match codec.decode(pass) {
+                    Ok(seat) => println!(
+                        "{}: row {}, column {}, id {}",
+                        pass, seat.row, seat.col, seat.id
+                    ),
+                    Err(e) => {
+                        eprintln!("{}: {}", pass, e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Some("encode") => {
+            let numbers: Result<Vec<u64>, _> = operands.iter().map(|n| n.parse()).collect();
+            let pass = match numbers.as_deref() {
+                Ok(&[id]) => codec.encode_id(id),
+                Ok(&[row, col]) => codec.encode(row, col),
+                _ => {
+                    eprintln!("encode takes an id, or a row and a column");
+                    std::process::exit(1);
+                }
+            };
+            match pass {
+                Ok(pass) => println!("{}", pass),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        // `manifest` reconciles the puzzle input, or `manifest passes.txt`
+        Some("manifest") => {
+            let passes = match operands.first() {
+                Some(path) => match std::fs::read_to_string(path) {
+                    Ok(passes) => passes,
+                    Err(e) => {
+                        eprintln!("{}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                },
+                None => FULL_INPUT.to_owned(),
+            };
+            let analysis = manifest::analyse(full_plane(), &passes);
+            print!("{}", analysis);
+            println!("free between two taken seats: {:?}", analysis.candidates);
+            println!("empty runs: {:?}", analysis.empty_runs);
+            println!(
+                "empty rows: {:?} at the front, {:?} at the back",
+                analysis.empty_front_rows, analysis.empty_back_rows
+            );
+            for duplicate in &analysis.duplicates {
+                println!("seat {} is on lines {:?}", duplicate.id, duplicate.lines);
+            }
+            for rejected in &analysis.rejected {
+                println!(
+                    "line {} ({}): {:?}",
+                    rejected.line, rejected.pass, rejected.reason
+                );
+            }
+        }
+        // `simulate STRATEGY [PASSENGERS [SEED]]` prints the passes, with statistics on stderr
+        Some("simulate") => {
+            use rand::SeedableRng;
+
+            let strategy = match operands.first().and_then(|name| simulation::by_name(name)) {
+                Some(strategy) => strategy,
+                None => {
+                    let names: Vec<_> = simulation::builtin().iter().map(|s| s.name()).collect();
+                    eprintln!("simulate needs a strategy: {}", names.join(", "));
+                    std::process::exit(1);
+                }
+            };
+            let plane = full_plane();
+            let numbers: Result<Vec<u64>, _> = operands[1..].iter().map(|n| n.parse()).collect();
+            let (count, seed) = match numbers.as_deref() {
+                Ok(&[]) => (plane.seats() * 9 / 10, 2020),
+                Ok(&[count]) => (count, 2020),
+                Ok(&[count, seed]) => (count, seed),
+                _ => {
+                    eprintln!("simulate takes a passenger count and a seed");
+                    std::process::exit(1);
+                }
+            };
+            if count > plane.seats() {
+                eprintln!(
+                    "{} passengers won't fit on a plane of {} seats",
+                    count,
+                    plane.seats()
+                );
+                std::process::exit(1);
+            }
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let passengers = simulation::passengers(count as usize, 4, &mut rng);
+            let boarding = simulation::simulate(plane, passengers, &*strategy, &mut rng);
+            for pass in &boarding.passes {
+                println!("{}", pass);
+            }
+            eprintln!("{}", boarding.stats);
+        }
+        _ => println!("Hello, world! (see tests for actual solutions)"),
+    }
 }
 
 const FULL_INPUT: &str = include_str!("input.txt");
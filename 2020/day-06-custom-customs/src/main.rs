@@ -1,5 +1,91 @@
+#![allow(dead_code)]
+
 fn main() {
-    println!("Hello, world!");
+    // `query 'anyone - everyone' [answers.txt]` prints what the query picks out of each group
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("query") => {
+            let query: answers::Query = match args.get(1).map(|q| q.parse()) {
+                Some(Ok(query)) => query,
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                None => {
+                    eprintln!("query needs a query, like 'anyone - everyone'");
+                    std::process::exit(1);
+                }
+            };
+            let input = match args.get(2) {
+                Some(path) => match std::fs::read_to_string(path) {
+                    Ok(input) => input,
+                    Err(e) => {
+                        eprintln!("{}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                },
+                None => FULL.to_owned(),
+            };
+            let groups = match answers::groups(&input) {
+                Ok(groups) => groups,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            for (i, group) in groups.iter().enumerate() {
+                println!("group {}: {:?}", i + 1, query.eval(group));
+            }
+            println!("total: {}", query.sum(&groups));
+        }
+        // `stats [--csv TABLE] [answers.txt]` prints every table, or one as CSV
+        Some("stats") => {
+            let mut operands = &args[1..];
+            let mut csv = None;
+            if operands.first().map(String::as_str) == Some("--csv") {
+                match operands.get(1).map(|table| table.parse::<stats::Table>()) {
+                    Some(Ok(table)) => csv = Some(table),
+                    Some(Err(e)) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                    None => {
+                        eprintln!("--csv needs a table");
+                        std::process::exit(1);
+                    }
+                }
+                operands = &operands[2..];
+            }
+            let input = match operands.first() {
+                Some(path) => match std::fs::read_to_string(path) {
+                    Ok(input) => input,
+                    Err(e) => {
+                        eprintln!("{}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                },
+                None => FULL.to_owned(),
+            };
+            let groups = match answers::groups(&input) {
+                Ok(groups) => groups,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let report = stats::Report::new(&groups);
+            let mut out = std::io::stdout().lock();
+            let written = match csv {
+                Some(table) => report.write_csv(table, &mut out),
+                None => report.write_report(&mut out),
+            };
+            if let Err(e) = written {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        _ => println!("Hello, world!"),
+    }
 }
 
 const FULL: &str = include_str!("input.txt");
@@ -19,79 +105,678 @@ a
 
 b"#;
 
-mod part_one {
-    use im::HashSet;
-    use std::fmt;
+/// Answer sets as bitmasks, and queries over a group's answers.
+mod answers {
+    use std::{fmt, ops, str::FromStr};
+
+    /// The questions someone answered yes to, one bit per letter from `a` to `z`.
+    #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct Answers(u32);
+
+    impl Answers {
+        pub const NONE: Answers = Answers(0);
+        pub const ALL: Answers = Answers((1 << 26) - 1);
+
+        pub fn parse(line: &str) -> Result<Self, InvalidQuestion> {
+            line.chars().try_fold(Self::NONE, |answers, c| match c {
+                'a'..='z' => Ok(answers | Self::question(c as u8)),
+                _ => Err(InvalidQuestion(c)),
+            })
+        }
+
+        fn question(letter: u8) -> Self {
+            Answers(1 << (letter - b'a'))
+        }
 
-    pub struct Answers(HashSet<u8>);
+        pub fn contains(self, letter: u8) -> bool {
+            letter.is_ascii_lowercase() && self & Self::question(letter) != Self::NONE
+        }
+
+        pub fn len(self) -> usize {
+            self.0.count_ones() as usize
+        }
+
+        /// The letters, in order.
+        pub fn iter(self) -> impl Iterator<Item = u8> {
+            (b'a'..=b'z').filter(move |&letter| self.contains(letter))
+        }
+    }
 
     impl fmt::Debug for Answers {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            for &answer in &self.0 {
+            for answer in self.iter() {
                 write!(f, "{}", answer as char)?;
             }
             Ok(())
         }
     }
 
-    fn calc(s: &str) -> usize {
-        let answer: usize = s
-            .split("\n\n")
-            .map(|group| {
-                HashSet::<u8>::unions(
-                    group
-                        .lines()
-                        .map(|line| line.as_bytes().iter().copied().collect()),
-                )
-                .len()
-            })
-            .sum();
+    impl ops::BitOr for Answers {
+        type Output = Self;
+
+        fn bitor(self, rhs: Self) -> Self {
+            Answers(self.0 | rhs.0)
+        }
+    }
+
+    impl ops::BitAnd for Answers {
+        type Output = Self;
+
+        fn bitand(self, rhs: Self) -> Self {
+            Answers(self.0 & rhs.0)
+        }
+    }
+
+    impl ops::BitXor for Answers {
+        type Output = Self;
+
+        fn bitxor(self, rhs: Self) -> Self {
+            Answers(self.0 ^ rhs.0)
+        }
+    }
+
+    impl ops::Sub for Answers {
+        type Output = Self;
+
+        fn sub(self, rhs: Self) -> Self {
+            Answers(self.0 & !rhs.0)
+        }
+    }
+
+    /// Every question not in `self`.
+    impl ops::Not for Answers {
+        type Output = Self;
+
+        fn not(self) -> Self {
+            Self::ALL - self
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InvalidQuestion(pub char);
 
-        answer
+    impl fmt::Display for InvalidQuestion {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "questions are a to z, not {:?}", self.0)
+        }
+    }
+
+    impl std::error::Error for InvalidQuestion {}
+
+    /// One person per line, with groups separated by blank lines.
+    pub fn groups(s: &str) -> Result<Vec<Vec<Answers>>, InvalidQuestion> {
+        let mut groups = vec![];
+        let mut group = vec![];
+        for line in s.lines().map(str::trim) {
+            match line {
+                "" if group.is_empty() => {}
+                "" => groups.push(std::mem::take(&mut group)),
+                line => group.push(Answers::parse(line)?),
+            }
+        }
+        if !group.is_empty() {
+            groups.push(group);
+        }
+        Ok(groups)
+    }
+
+    /// Something to work out for each group.
+    ///
+    /// Written like `anyone - everyone` or `at-least-2 & (xor | nobody)`. The operators are `|`,
+    /// `&`, `^` and `-`, all equally binding and read left to right, so use brackets to group. As
+    /// names have dashes in, `-` needs a space or bracket before it.
+    /// The aggregates are the variants below in kebab case, with `xor` for [`Query::Odd`] and
+    /// the count written into the name for [`Query::AtLeast`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Query {
+        /// Answered by at least one person
+        Anyone,
+        /// Answered by every person, none for an empty group
+        Everyone,
+        /// Answered by nobody at all
+        Nobody,
+        /// Answered by at least this many people
+        AtLeast(usize),
+        /// Answered by just one person
+        ExactlyOne,
+        /// Answered by an odd number of people, the symmetric difference of everyone's answers
+        Odd,
+        Union(Box<Query>, Box<Query>),
+        Intersection(Box<Query>, Box<Query>),
+        SymmetricDifference(Box<Query>, Box<Query>),
+        Difference(Box<Query>, Box<Query>),
+    }
+
+    impl Query {
+        pub fn eval(&self, group: &[Answers]) -> Answers {
+            let all =
+                |f: fn(Answers, Answers) -> Answers, start| group.iter().copied().fold(start, f);
+            match self {
+                Query::Anyone => all(ops::BitOr::bitor, Answers::NONE),
+                Query::Everyone if group.is_empty() => Answers::NONE,
+                Query::Everyone => all(ops::BitAnd::bitand, Answers::ALL),
+                Query::Nobody => !all(ops::BitOr::bitor, Answers::NONE),
+                Query::AtLeast(0) => Answers::ALL,
+                Query::AtLeast(k) => {
+                    let mut counts = [0; 26];
+                    for answers in group {
+                        for letter in answers.iter() {
+                            counts[(letter - b'a') as usize] += 1;
+                        }
+                    }
+                    (b'a'..=b'z')
+                        .filter(|&letter| counts[(letter - b'a') as usize] >= *k)
+                        .fold(Answers::NONE, |acc, letter| acc | Answers::question(letter))
+                }
+                Query::ExactlyOne => {
+                    let (mut once, mut twice) = (Answers::NONE, Answers::NONE);
+                    for &answers in group {
+                        twice = twice | (once & answers);
+                        once = once | answers;
+                    }
+                    once - twice
+                }
+                Query::Odd => all(ops::BitXor::bitxor, Answers::NONE),
+                Query::Union(a, b) => a.eval(group) | b.eval(group),
+                Query::Intersection(a, b) => a.eval(group) & b.eval(group),
+                Query::SymmetricDifference(a, b) => a.eval(group) ^ b.eval(group),
+                Query::Difference(a, b) => a.eval(group) - b.eval(group),
+            }
+        }
+
+        /// The number of questions this query picks out, summed over every group.
+        pub fn sum(&self, groups: &[Vec<Answers>]) -> usize {
+            groups.iter().map(|group| self.eval(group).len()).sum()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct QueryError(pub String);
+
+    impl fmt::Display for QueryError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "bad query: {}", self.0)
+        }
+    }
+
+    impl std::error::Error for QueryError {}
+
+    impl FromStr for Query {
+        type Err = QueryError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let tokens = tokenize(s);
+            let mut tokens = tokens.iter().peekable();
+            let query = expr(&mut tokens)?;
+            match tokens.next() {
+                None => Ok(query),
+                Some(token) => Err(QueryError(format!("unexpected {:?}", token))),
+            }
+        }
+    }
+
+    /// Words, and the operators and brackets on their own.
+    fn tokenize(s: &str) -> Vec<String> {
+        let mut tokens: Vec<String> = vec![];
+        let mut word = false;
+        for c in s.chars() {
+            match c {
+                // part of a name like `exactly-one`
+                '-' if word => tokens.last_mut().unwrap().push(c),
+                '|' | '&' | '^' | '-' | '(' | ')' => {
+                    tokens.push(c.to_string());
+                    word = false;
+                }
+                c if c.is_whitespace() => word = false,
+                c => {
+                    match word {
+                        true => tokens.last_mut().unwrap().push(c),
+                        false => tokens.push(c.to_string()),
+                    }
+                    word = true;
+                }
+            }
+        }
+        tokens
+    }
+
+    type Tokens<'a> = std::iter::Peekable<std::slice::Iter<'a, String>>;
+
+    fn expr(tokens: &mut Tokens) -> Result<Query, QueryError> {
+        let mut query = operand(tokens)?;
+        while let Some(op) = tokens.peek() {
+            let combine = match op.as_str() {
+                "|" => Query::Union,
+                "&" => Query::Intersection,
+                "^" => Query::SymmetricDifference,
+                "-" => Query::Difference,
+                _ => break,
+            };
+            tokens.next();
+            query = combine(Box::new(query), Box::new(operand(tokens)?));
+        }
+        Ok(query)
+    }
+
+    fn operand(tokens: &mut Tokens) -> Result<Query, QueryError> {
+        let token = tokens
+            .next()
+            .ok_or_else(|| QueryError("expected an aggregate or \"(\"".to_owned()))?;
+        Ok(match token.as_str() {
+            "(" => {
+                let query = expr(tokens)?;
+                match tokens.next().map(String::as_str) {
+                    Some(")") => query,
+                    _ => return Err(QueryError("missing \")\"".to_owned())),
+                }
+            }
+            "anyone" => Query::Anyone,
+            "everyone" => Query::Everyone,
+            "nobody" => Query::Nobody,
+            "exactly-one" => Query::ExactlyOne,
+            "xor" => Query::Odd,
+            word => match word.strip_prefix("at-least-").map(str::parse) {
+                Some(Ok(k)) => Query::AtLeast(k),
+                _ => return Err(QueryError(format!("unknown aggregate {:?}", word))),
+            },
+        })
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
 
+        fn answers(s: &str) -> Answers {
+            Answers::parse(s).unwrap()
+        }
+
         #[test]
-        fn test_example() {
-            assert_eq!(calc(super::super::EXAMPLE), 11);
+        fn test_answers() {
+            let abc = answers("cab");
+            assert_eq!(abc.len(), 3);
+            assert_eq!(format!("{:?}", abc), "abc");
+            assert!(abc.contains(b'b') && !abc.contains(b'z') && !abc.contains(b'!'));
+            assert_eq!(format!("{:?}", abc ^ answers("cd")), "abd");
+            assert_eq!((!abc).len(), 23);
+            assert_eq!(Answers::parse("aB"), Err(InvalidQuestion('B')));
+            assert_eq!(Answers::ALL.len(), 26);
+        }
+
+        #[test]
+        fn test_aggregates() {
+            let group = [answers("abc"), answers("abd"), answers("ae"), answers("a")];
+            let eval = |query: &str| format!("{:?}", query.parse::<Query>().unwrap().eval(&group));
+
+            assert_eq!(eval("anyone"), "abcde");
+            assert_eq!(eval("everyone"), "a");
+            assert_eq!(eval("at-least-2"), "ab");
+            assert_eq!(eval("at-least-0").len(), 26);
+            assert_eq!(eval("exactly-one"), "cde");
+            assert_eq!(eval("xor"), "cde");
+            assert_eq!(eval("nobody"), "fghijklmnopqrstuvwxyz");
+            assert_eq!(eval("anyone - everyone"), "bcde");
+            assert_eq!(eval("at-least-2 ^ xor"), "abcde");
+            assert_eq!(eval("anyone - (at-least-2 | everyone)"), "cde");
+        }
+
+        #[test]
+        fn test_query_errors() {
+            for bad in [
+                "",
+                "anyone |",
+                "(anyone",
+                "anyone)",
+                "at-least-x",
+                "anyone-everyone",
+            ] {
+                assert!(bad.parse::<Query>().is_err(), "{:?}", bad);
+            }
+            assert_eq!(
+                "anyone -everyone".parse::<Query>(),
+                "anyone - everyone".parse()
+            );
         }
 
         #[test]
         fn test_full() {
-            assert_eq!(calc(super::super::FULL), 6291);
+            let groups = groups(super::super::FULL).unwrap();
+            assert_eq!(Query::Anyone.sum(&groups), 6291);
+            assert_eq!(Query::Everyone.sum(&groups), 3052);
+            // every question is answered by somebody or nobody
+            let either: Query = "anyone | nobody".parse().unwrap();
+            assert_eq!(either.sum(&groups), 26 * groups.len());
         }
     }
 }
 
-mod part_two {
-    use im::HashSet;
-    use std::fmt;
+/// Statistics over every group's answers.
+mod stats {
+    use super::answers::{Answers, Query};
+    use std::{collections::BTreeMap, io};
 
-    pub struct Answers(HashSet<u8>);
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct GroupStats {
+        pub size: usize,
+        pub anyone: Answers,
+        pub everyone: Answers,
+    }
 
-    impl fmt::Debug for Answers {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            for &answer in &self.0 {
-                write!(f, "{}", answer as char)?;
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct QuestionStats {
+        /// People answering yes
+        pub people: usize,
+        /// Groups where anyone answered yes
+        pub groups: usize,
+        /// Groups where everyone answered yes
+        pub unanimous: usize,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Report {
+        pub people: usize,
+        /// Indexed from `a`
+        pub questions: [QuestionStats; 26],
+        /// How many groups there are of each size
+        pub sizes: BTreeMap<usize, usize>,
+        pub groups: Vec<GroupStats>,
+    }
+
+    /// The tables a report is made of.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Table {
+        Questions,
+        Sizes,
+        Unanimous,
+        Groups,
+    }
+
+    impl Table {
+        pub const ALL: [Table; 4] = [
+            Table::Questions,
+            Table::Sizes,
+            Table::Unanimous,
+            Table::Groups,
+        ];
+    }
+
+    impl std::str::FromStr for Table {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "questions" => Ok(Table::Questions),
+                "sizes" => Ok(Table::Sizes),
+                "unanimous" => Ok(Table::Unanimous),
+                "groups" => Ok(Table::Groups),
+                _ => Err(format!(
+                    "no table {:?}: questions, sizes, unanimous or groups",
+                    s
+                )),
+            }
+        }
+    }
+
+    impl Report {
+        pub fn new(groups: &[Vec<Answers>]) -> Self {
+            let mut report = Report {
+                people: 0,
+                questions: Default::default(),
+                sizes: BTreeMap::new(),
+                groups: vec![],
+            };
+            for group in groups {
+                let stats = GroupStats {
+                    size: group.len(),
+                    anyone: Query::Anyone.eval(group),
+                    everyone: Query::Everyone.eval(group),
+                };
+                for (letter, question) in (b'a'..=b'z').zip(&mut report.questions) {
+                    question.people += group.iter().filter(|a| a.contains(letter)).count();
+                    question.groups += stats.anyone.contains(letter) as usize;
+                    question.unanimous += stats.everyone.contains(letter) as usize;
+                }
+                report.people += group.len();
+                *report.sizes.entry(group.len()).or_default() += 1;
+                report.groups.push(stats);
+            }
+            report
+        }
+
+        /// Questions some group answered unanimously, most often first.
+        pub fn most_unanimous(&self) -> Vec<(u8, usize)> {
+            let mut ranked: Vec<_> = (b'a'..=b'z')
+                .zip(self.questions.iter().map(|q| q.unanimous))
+                .filter(|&(_, unanimous)| unanimous > 0)
+                .collect();
+            ranked.sort_by_key(|&(letter, unanimous)| (std::cmp::Reverse(unanimous), letter));
+            ranked
+        }
+
+        /// Headers, then rows.
+        fn rows(&self, table: Table) -> (Vec<&'static str>, Vec<Vec<String>>) {
+            let share = |n: usize, of: usize| match of {
+                0 => "0.0".to_owned(),
+                of => format!("{:.1}", 100.0 * n as f64 / of as f64),
+            };
+            let groups = self.groups.len();
+            match table {
+                Table::Questions => (
+                    vec!["question", "people", "% people", "groups", "unanimous"],
+                    (b'a'..=b'z')
+                        .zip(&self.questions)
+                        .map(|(letter, q)| {
+                            vec![
+                                (letter as char).to_string(),
+                                q.people.to_string(),
+                                share(q.people, self.people),
+                                q.groups.to_string(),
+                                q.unanimous.to_string(),
+                            ]
+                        })
+                        .collect(),
+                ),
+                Table::Sizes => (
+                    vec!["size", "groups", "% groups"],
+                    self.sizes
+                        .iter()
+                        .map(|(size, &n)| vec![size.to_string(), n.to_string(), share(n, groups)])
+                        .collect(),
+                ),
+                Table::Unanimous => (
+                    vec!["question", "unanimous", "% groups"],
+                    self.most_unanimous()
+                        .into_iter()
+                        .map(|(letter, n)| {
+                            vec![
+                                (letter as char).to_string(),
+                                n.to_string(),
+                                share(n, groups),
+                            ]
+                        })
+                        .collect(),
+                ),
+                Table::Groups => (
+                    vec![
+                        "group",
+                        "size",
+                        "anyone",
+                        "everyone",
+                        "# anyone",
+                        "# everyone",
+                    ],
+                    self.groups
+                        .iter()
+                        .enumerate()
+                        .map(|(i, g)| {
+                            vec![
+                                (i + 1).to_string(),
+                                g.size.to_string(),
+                                format!("{:?}", g.anyone),
+                                format!("{:?}", g.everyone),
+                                g.anyone.len().to_string(),
+                                g.everyone.len().to_string(),
+                            ]
+                        })
+                        .collect(),
+                ),
+            }
+        }
+
+        /// One table with its columns lined up, text left and numbers right.
+        pub fn write_text(&self, table: Table, out: &mut impl io::Write) -> io::Result<()> {
+            let (headers, rows) = self.rows(table);
+            let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+            for row in &rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+            let numeric: Vec<bool> = (0..headers.len())
+                .map(|i| rows.iter().all(|row| row[i].parse::<f64>().is_ok()))
+                .collect();
+
+            let line = |cells: Vec<&str>| {
+                let cells: Vec<String> = cells
+                    .iter()
+                    .zip(&widths)
+                    .zip(&numeric)
+                    .map(|((cell, &width), &numeric)| match numeric {
+                        true => format!("{:>width$}", cell, width = width),
+                        false => format!("{:<width$}", cell, width = width),
+                    })
+                    .collect();
+                cells.join("  ").trim_end().to_owned()
+            };
+            writeln!(out, "{}", line(headers.clone()))?;
+            for row in &rows {
+                writeln!(out, "{}", line(row.iter().map(String::as_str).collect()))?;
+            }
+            Ok(())
+        }
+
+        /// One table as CSV. No cell needs quoting: they're all letters and numbers.
+        pub fn write_csv(&self, table: Table, out: &mut impl io::Write) -> io::Result<()> {
+            let (headers, rows) = self.rows(table);
+            writeln!(out, "{}", headers.join(","))?;
+            for row in rows {
+                writeln!(out, "{}", row.join(","))?;
+            }
+            Ok(())
+        }
+
+        /// A summary line, then every table under a heading.
+        pub fn write_report(&self, out: &mut impl io::Write) -> io::Result<()> {
+            writeln!(
+                out,
+                "{} people in {} groups",
+                self.people,
+                self.groups.len()
+            )?;
+            for table in Table::ALL {
+                let heading = match table {
+                    Table::Questions => "Questions",
+                    Table::Sizes => "Group sizes",
+                    Table::Unanimous => "Most often unanimous",
+                    Table::Groups => "Groups",
+                };
+                writeln!(out, "\n{}\n", heading)?;
+                self.write_text(table, out)?;
             }
             Ok(())
         }
     }
 
+    #[cfg(test)]
+    mod tests {
+        use super::super::answers;
+        use super::*;
+
+        fn example() -> Report {
+            Report::new(&answers::groups(super::super::EXAMPLE).unwrap())
+        }
+
+        #[test]
+        fn test_example() {
+            let report = example();
+            assert_eq!(report.people, 11);
+            assert_eq!(
+                report.questions[0],
+                QuestionStats {
+                    people: 8,
+                    groups: 4,
+                    unanimous: 3
+                }
+            );
+            assert_eq!(
+                report.sizes.clone().into_iter().collect::<Vec<_>>(),
+                [(1, 2), (2, 1), (3, 1), (4, 1)]
+            );
+            assert_eq!(report.most_unanimous(), [(b'a', 3), (b'b', 2), (b'c', 1)]);
+            // the same sums as the puzzle
+            let anyone: usize = report.groups.iter().map(|g| g.anyone.len()).sum();
+            let everyone: usize = report.groups.iter().map(|g| g.everyone.len()).sum();
+            assert_eq!((anyone, everyone), (11, 6));
+        }
+
+        #[test]
+        fn test_output() {
+            let report = example();
+            let render = |f: fn(&Report, Table, &mut Vec<u8>) -> io::Result<()>, table| {
+                let mut out = vec![];
+                f(&report, table, &mut out).unwrap();
+                String::from_utf8(out).unwrap()
+            };
+
+            assert_eq!(
+                render(Report::write_text, Table::Sizes),
+                "size  groups  % groups\n   1       2      40.0\n   2       1      20.0\n   3       1      20.0\n   4       1      20.0\n"
+            );
+            assert_eq!(
+                render(Report::write_csv, Table::Groups).lines().nth(2),
+                Some("2,3,abc,,3,0")
+            );
+            assert_eq!(
+                render(Report::write_csv, Table::Questions).lines().count(),
+                27
+            );
+
+            let mut out = vec![];
+            report.write_report(&mut out).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.starts_with("11 people in 5 groups\n"));
+            assert!(out.contains("\nMost often unanimous\n\nquestion  unanimous  % groups\na                 3      60.0\n"));
+        }
+    }
+}
+
+mod part_one {
+    use super::answers::{self, Query};
+
     fn calc(s: &str) -> usize {
-        s.split("\n\n")
-            .map(|group| {
-                group
-                    .lines()
-                    .map(|line| line.as_bytes().iter().copied().collect())
-                    .reduce(|acc: HashSet<u8>, x| acc.intersection(x))
-                    .unwrap_or_default()
-                    .len()
-            })
-            .sum()
+        Query::Anyone.sum(&answers::groups(s).expect("puzzle input is a to z"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_example() {
+            assert_eq!(calc(super::super::EXAMPLE), 11);
+        }
+
+        #[test]
+        fn test_full() {
+            assert_eq!(calc(super::super::FULL), 6291);
+        }
+    }
+}
+
+mod part_two {
+    use super::answers::{self, Query};
+
+    fn calc(s: &str) -> usize {
+        Query::Everyone.sum(&answers::groups(s).expect("puzzle input is a to z"))
     }
 
     #[cfg(test)]